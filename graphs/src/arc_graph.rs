@@ -0,0 +1,172 @@
+use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
+use std::thread;
+use graph::Graph;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+struct Node {
+    datum: &'static str,
+    edges: Vec<Arc<Mutex<Node>>>,
+}
+
+impl Node {
+    fn new(datum: &'static str) -> Arc<Mutex<Node>> {
+        Arc::new(Mutex::new(Node {
+            datum,
+            edges: Vec::new(),
+        }))
+    }
+
+    fn first(&self) -> Option<Arc<Mutex<Node>>> {
+        self.edges.first().cloned()
+    }
+}
+
+// Unlike the `Rc<RefCell<_>>` version, this takes the `Arc` and only holds a
+// node's lock long enough to copy out its datum and edges. A `Mutex` can't be
+// locked twice, so holding the lock while visiting the children would deadlock
+// on the `C -> A` back-edge.
+fn traverse<F>(node: &Arc<Mutex<Node>>, f: &F, seen: &mut HashSet<&'static str>)
+    where F: Fn(&'static str)
+{
+    let (datum, edges) = {
+        let node = node.lock().unwrap();
+        (node.datum, node.edges.clone())
+    };
+    if !seen.insert(datum) {
+        return;
+    }
+    f(datum);
+    for n in &edges {
+        traverse(n, f, seen);
+    }
+}
+
+// Visits the children of each node in parallel, so `f` may be called from
+// several threads and in no particular order. A node is claimed in the shared
+// seen-set before it is visited, so each node is visited exactly once.
+#[cfg(feature = "rayon")]
+fn par_traverse<F>(node: &Arc<Mutex<Node>>, f: &F)
+    where F: Fn(&'static str) + Sync
+{
+    par_visit(node, f, &Mutex::new(HashSet::new()));
+}
+
+#[cfg(feature = "rayon")]
+fn par_visit<F>(node: &Arc<Mutex<Node>>, f: &F, seen: &Mutex<HashSet<&'static str>>)
+    where F: Fn(&'static str) + Sync
+{
+    let (datum, edges) = {
+        let node = node.lock().unwrap();
+        (node.datum, node.edges.clone())
+    };
+    if !seen.lock().unwrap().insert(datum) {
+        return;
+    }
+    f(datum);
+    edges.par_iter().for_each(|n| par_visit(n, f, seen));
+}
+
+impl Graph for Arc<Mutex<Node>> {
+    type NodeRef = Arc<Mutex<Node>>;
+    type Datum = &'static str;
+
+    fn traverse<F>(&self, f: &F)
+        where F: Fn(&&'static str)
+    {
+        traverse(self, &|d| f(&d), &mut HashSet::new());
+    }
+
+    fn first(&self) -> Option<Arc<Mutex<Node>>> {
+        self.lock().unwrap().first()
+    }
+
+    fn node_count(&self) -> usize {
+        let mut seen = HashSet::new();
+        traverse(self, &|_| {}, &mut seen);
+        seen.len()
+    }
+}
+
+fn foo(node: &Node) {
+    println!("foo: {}", node.datum);
+}
+
+fn init() -> Arc<Mutex<Node>> {
+    let root = Node::new("A");
+
+    let b = Node::new("B");
+    let c = Node::new("C");
+    let d = Node::new("D");
+    let e = Node::new("E");
+    let f = Node::new("F");
+
+    {
+        let mut mut_root = root.lock().unwrap();
+        mut_root.edges.push(b.clone());
+        mut_root.edges.push(c.clone());
+        mut_root.edges.push(d.clone());
+
+        let mut mut_c = c.lock().unwrap();
+        mut_c.edges.push(e.clone());
+        mut_c.edges.push(f.clone());
+        mut_c.edges.push(root.clone());
+    }
+
+    root
+}
+
+pub fn main() {
+    let g = init();
+    // The graph is `Send + Sync`, so we can traverse it from another thread.
+    let g2 = g.clone();
+    thread::spawn(move || {
+        traverse(&g2, &|d| println!("{}", d), &mut HashSet::new());
+    }).join().unwrap();
+    let f = g.lock().unwrap().first();
+    if let Some(f) = f {
+        foo(&f.lock().unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph;
+
+    #[test]
+    fn traverses_from_several_threads() {
+        let g = init();
+        let handles: Vec<_> = (0..4).map(|_| {
+            let g = g.clone();
+            thread::spawn(move || {
+                let v = Mutex::new(Vec::new());
+                traverse(&g, &|d| v.lock().unwrap().push(d), &mut HashSet::new());
+                v.into_inner().unwrap()
+            })
+        }).collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), vec!["A", "B", "C", "E", "F", "D"]);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_traverse_visits_each_node_once() {
+        let g = init();
+        let seen = Mutex::new(HashSet::new());
+        let count = Mutex::new(0);
+        par_traverse(&g, &|d| {
+            seen.lock().unwrap().insert(d);
+            *count.lock().unwrap() += 1;
+        });
+        assert_eq!(seen.into_inner().unwrap().len(), 6);
+        assert_eq!(count.into_inner().unwrap(), 6);
+    }
+
+    #[test]
+    fn implements_graph() {
+        assert_eq!(graph::tests::check(&init()), 6);
+    }
+}
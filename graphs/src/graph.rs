@@ -0,0 +1,33 @@
+// The operations shared by all of the graph backends, so that code using a
+// graph can be written once rather than once per backend. Each backend
+// implements this for its handle to a node, e.g., `Rc<RefCell<Node>>`.
+pub trait Graph {
+    // A handle to a node, as returned by `first`.
+    type NodeRef;
+    type Datum;
+
+    // A pre-order, depth-first traversal of the nodes reachable from `self`.
+    fn traverse<F>(&self, f: &F)
+        where F: Fn(&Self::Datum);
+
+    fn first(&self) -> Option<Self::NodeRef>;
+
+    // The number of distinct nodes reachable from `self`, including `self`.
+    fn node_count(&self) -> usize;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // Checks that `traverse` and `node_count` agree and that `g` has a first
+    // neighbour, using nothing but the `Graph` trait. Returns the node count.
+    pub(crate) fn check<G: Graph>(g: &G) -> usize {
+        let count = Cell::new(0);
+        g.traverse(&|_| count.set(count.get() + 1));
+        assert_eq!(g.node_count(), count.get());
+        assert!(g.first().is_some());
+        count.get()
+    }
+}
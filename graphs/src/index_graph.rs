@@ -0,0 +1,114 @@
+// Nodes live in a `Vec` owned by the graph and refer to each other by index,
+// so there are no `Rc`s to clone or `RefCell`s to borrow when following an
+// edge. The price is that a `NodeId` is only meaningful with the graph which
+// handed it out, and the compiler can't check that for us.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct NodeId(usize);
+
+struct NodeData {
+    datum: &'static str,
+}
+
+struct IndexGraph {
+    nodes: Vec<NodeData>,
+    // `edges[i]` holds the indices of the nodes which node `i` has edges to.
+    edges: Vec<Vec<usize>>,
+}
+
+impl IndexGraph {
+    fn new() -> IndexGraph {
+        IndexGraph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    fn add_node(&mut self, datum: &'static str) -> NodeId {
+        self.nodes.push(NodeData { datum });
+        self.edges.push(Vec::new());
+        NodeId(self.nodes.len() - 1)
+    }
+
+    // Panics if either node isn't in this graph.
+    fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        assert!(to.0 < self.nodes.len());
+        self.edges[from.0].push(to.0);
+    }
+
+    fn neighbors(&self, id: NodeId) -> &[usize] {
+        &self.edges[id.0]
+    }
+
+    fn datum(&self, id: NodeId) -> &'static str {
+        self.nodes[id.0].datum
+    }
+
+    // A pre-order, depth-first traversal from `start`, like the other
+    // backends. Indices make it cheap to keep track of which nodes we've seen.
+    fn traverse<F>(&self, start: NodeId, f: &F)
+        where F: Fn(&'static str)
+    {
+        self.visit(start.0, f, &mut vec![false; self.nodes.len()]);
+    }
+
+    fn visit<F>(&self, i: usize, f: &F, seen: &mut [bool])
+        where F: Fn(&'static str)
+    {
+        if seen[i] {
+            return;
+        }
+        seen[i] = true;
+        f(self.nodes[i].datum);
+        for &n in &self.edges[i] {
+            self.visit(n, f, seen);
+        }
+    }
+}
+
+fn init() -> (IndexGraph, NodeId) {
+    let mut g = IndexGraph::new();
+    let root = g.add_node("A");
+
+    let b = g.add_node("B");
+    let c = g.add_node("C");
+    let d = g.add_node("D");
+    let e = g.add_node("E");
+    let f = g.add_node("F");
+
+    g.add_edge(root, b);
+    g.add_edge(root, c);
+    g.add_edge(root, d);
+    g.add_edge(c, e);
+    g.add_edge(c, f);
+    g.add_edge(c, root);
+
+    (g, root)
+}
+
+pub fn main() {
+    let (g, root) = init();
+    g.traverse(root, &|d| println!("{}", d));
+    if let Some(&first) = g.neighbors(root).first() {
+        println!("first: {}", g.datum(NodeId(first)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::Graph;
+    use rc_graph;
+    use std::cell::RefCell;
+
+    #[test]
+    fn traverse_matches_rc_graph() {
+        let expected = RefCell::new(Vec::new());
+        rc_graph::init().traverse(&|d: &&str| expected.borrow_mut().push(*d));
+        let (g, root) = init();
+        let v = RefCell::new(Vec::new());
+        g.traverse(root, &|d| v.borrow_mut().push(d));
+        assert_eq!(v.into_inner(), expected.into_inner());
+        assert_eq!(g.neighbors(NodeId(2)), &[4, 5, 0]);
+    }
+}
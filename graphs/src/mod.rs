@@ -1,13 +1,40 @@
-#![feature(rustc_private)]
+// Not every graph operation is exercised by `main`, they are here to be read
+// and reused.
+#![cfg_attr(not(test), allow(dead_code))]
 
 extern crate typed_arena;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
+mod arc_graph;
+mod graph;
+mod index_graph;
 mod rc_graph;
 mod ref_graph;
+mod ref_graph_generic_mod;
+mod safe_ref_graph;
+mod undirected_graph;
+mod weighted_graph;
 
 fn main() {
     println!("\nRc<RefCell<Node>>:");
     rc_graph::main();
     println!("\n&Node and UnsafeCell:");
     ref_graph::main();
+    println!("\n&Node in a Graph<'a>:");
+    ref_graph_generic_mod::main();
+    println!("\n&Node and RefCell:");
+    safe_ref_graph::main();
+    println!("\nArc<Mutex<Node>>:");
+    arc_graph::main();
+    println!("\nWeighted Rc<RefCell<Node>>:");
+    weighted_graph::main();
+    println!("\nUndirected Rc<RefCell<Node>>:");
+    undirected_graph::main();
+    println!("\nIndices into a Vec:");
+    index_graph::main();
 }
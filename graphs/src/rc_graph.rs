@@ -1,44 +1,2546 @@
 
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::error::Error;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use graph::Graph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
 
-struct Node {
-    datum: &'static str,
-    edges: Vec<Rc<RefCell<Node>>>,
+// The datum defaults to a string label, as in the other examples. Other
+// modules can read the structure, e.g., `ref_graph::into_ref_graph`.
+pub(crate) struct Node<T = &'static str> {
+    pub(crate) datum: T,
+    pub(crate) edges: Vec<Rc<RefCell<Node<T>>>>,
+    // Edges which `break_cycles` has downgraded so that the graph can be
+    // freed. `neighbors` follows these as well as `edges` while their targets
+    // are alive, so queries see the same graph as before. Only `has_cycle` and
+    // `prune_unreachable`, which are about what can be freed, ignore them.
+    weak_edges: Vec<Weak<RefCell<Node<T>>>>,
 }
 
-impl Node {
-    fn new(datum: &'static str) -> Rc<RefCell<Node>> {
+// Nodes are identified by address rather than by datum, so that distinct nodes
+// with the same datum are not confused. A `&Node` borrowed from a `RefCell` has
+// the same address as the `RefCell`'s `as_ptr`, so `self as *const Node<T>`
+// and `id(&rc)` agree about which node is which.
+type NodeId<T> = *const Node<T>;
+
+fn id<T>(node: &Rc<RefCell<Node<T>>>) -> NodeId<T> {
+    node.as_ptr()
+}
+
+// The graph reachable from a node, flattened so that nodes can be referred to
+// by index. Nodes are in pre-order (the order `traverse` visits them), so the
+// start node is always index 0.
+struct Adjacency<T> {
+    data: Vec<T>,
+    edges: Vec<Vec<usize>>,
+}
+
+// The JSON form of a graph. Edges are pairs of indices into `nodes`, so shared
+// nodes and cycles are written out once. The first node is the start node.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct GraphJson<T> {
+    nodes: Vec<T>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl<T: Clone + Eq + Hash> Adjacency<T> {
+    fn empty() -> Adjacency<T> {
+        Adjacency {
+            data: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    // The same nodes with every edge pointing the other way.
+    fn reversed(&self) -> Adjacency<T> {
+        let mut edges = vec![Vec::new(); self.edges.len()];
+        for (from, targets) in self.edges.iter().enumerate() {
+            for &to in targets {
+                edges[to].push(from);
+            }
+        }
+        Adjacency {
+            data: self.data.clone(),
+            edges,
+        }
+    }
+
+    // Extends `path` in every way which doesn't revisit a node, recording the
+    // paths which reach `target`.
+    fn simple_paths(&self,
+                    target: &T,
+                    path: &mut Vec<usize>,
+                    on_path: &mut [bool],
+                    paths: &mut Vec<Vec<T>>) {
+        let last = *path.last().unwrap();
+        if self.data[last] == *target {
+            paths.push(path.iter().map(|&i| self.data[i].clone()).collect());
+            return;
+        }
+        for &next in &self.edges[last] {
+            if on_path[next] {
+                continue;
+            }
+            on_path[next] = true;
+            path.push(next);
+            self.simple_paths(target, path, on_path, paths);
+            path.pop();
+            on_path[next] = false;
+        }
+    }
+
+    // Tries to extend `path` until it includes every node, backtracking when
+    // it gets stuck. On success `path` holds the whole path.
+    fn extend_hamiltonian(&self, path: &mut Vec<usize>, on_path: &mut [bool]) -> bool {
+        if path.len() == self.data.len() {
+            return true;
+        }
+        let last = *path.last().unwrap();
+        for &next in &self.edges[last] {
+            if on_path[next] {
+                continue;
+            }
+            on_path[next] = true;
+            path.push(next);
+            if self.extend_hamiltonian(path, on_path) {
+                return true;
+            }
+            path.pop();
+            on_path[next] = false;
+        }
+        false
+    }
+
+    // For each node, the nodes it shares an edge with in either direction.
+    fn undirected(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = self.edges.clone();
+        for (from, targets) in self.edges.iter().enumerate() {
+            for &to in targets {
+                neighbors[to].push(from);
+            }
+        }
+        neighbors
+    }
+
+    // Like `undirected`, but each neighbour comes with the index of the edge
+    // joining them, counting edges in order of `edges`.
+    fn undirected_edges(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut neighbors = vec![Vec::new(); self.edges.len()];
+        let mut edge = 0;
+        for (from, targets) in self.edges.iter().enumerate() {
+            for &to in targets {
+                neighbors[from].push((to, edge));
+                neighbors[to].push((from, edge));
+                edge += 1;
+            }
+        }
+        neighbors
+    }
+
+    // Creates a fresh `Rc` graph from the flattened one, returning its nodes in
+    // the same order as `data`.
+    fn build(&self) -> Vec<Rc<RefCell<Node<T>>>> {
+        let nodes: Vec<_> = self.data.iter().map(|d| Node::new(d.clone())).collect();
+        for (from, targets) in self.edges.iter().enumerate() {
+            for &to in targets {
+                add_edge(&nodes[from], &nodes[to]);
+            }
+        }
+        nodes
+    }
+
+    // The number of edges into each node.
+    fn in_degrees(&self) -> Vec<usize> {
+        let mut in_degree = vec![0; self.data.len()];
+        for targets in &self.edges {
+            for &t in targets {
+                in_degree[t] += 1;
+            }
+        }
+        in_degree
+    }
+
+    // Kahn's algorithm: repeatedly emit a node with no remaining incoming
+    // edges. If we run out of such nodes early, the rest are in a cycle, and
+    // there is no order.
+    fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut in_degree = self.in_degrees();
+        let mut ready: VecDeque<_> = (0..self.data.len()).filter(|&i| in_degree[i] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(i) = ready.pop_front() {
+            order.push(i);
+            for &t in &self.edges[i] {
+                in_degree[t] -= 1;
+                if in_degree[t] == 0 {
+                    ready.push_back(t);
+                }
+            }
+        }
+        if order.len() < self.data.len() {
+            return None;
+        }
+        Some(order)
+    }
+
+    fn classify(&self) -> GraphClass {
+        if self.topological_order().is_none() {
+            return GraphClass::Cyclic;
+        }
+        let in_degrees = self.in_degrees();
+        if in_degrees.iter().any(|&d| d > 1) {
+            return GraphClass::Dag;
+        }
+        match in_degrees.iter().filter(|&&d| d == 0).count() {
+            1 => GraphClass::Tree,
+            _ => GraphClass::Forest,
+        }
+    }
+
+    // Appends each node reachable from `i` to `order` once everything reachable
+    // from it has been appended, skipping nodes already `seen`.
+    fn finish_order(&self, i: usize, seen: &mut [bool], order: &mut Vec<usize>) {
+        seen[i] = true;
+        for &n in &self.edges[i] {
+            if !seen[n] {
+                self.finish_order(n, seen, order);
+            }
+        }
+        order.push(i);
+    }
+
+    // When a depth-first search from node 0 first reaches each node and when
+    // it has finished with everything reachable from it. The clock ticks once
+    // for each of these events, so no two times are the same.
+    fn timestamps(&self) -> Vec<(usize, usize)> {
+        let mut times = vec![None; self.data.len()];
+        self.stamp(0, &mut 0, &mut times);
+        times.into_iter().flatten().collect()
+    }
+
+    fn stamp(&self, i: usize, clock: &mut usize, times: &mut [Option<(usize, usize)>]) {
+        let discovered = *clock;
+        times[i] = Some((discovered, discovered));
+        *clock += 1;
+        for &n in &self.edges[i] {
+            if times[n].is_none() {
+                self.stamp(n, clock, times);
+            }
+        }
+        times[i] = Some((discovered, *clock));
+        *clock += 1;
+    }
+
+    // The number of hops from `start` to each node, or `None` for nodes which
+    // can't be reached from it.
+    fn distances(&self, start: usize) -> Vec<Option<usize>> {
+        let mut dist = vec![None; self.data.len()];
+        dist[start] = Some(0);
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(i) = queue.pop_front() {
+            let d = dist[i].unwrap();
+            for &n in &self.edges[i] {
+                if dist[n].is_none() {
+                    dist[n] = Some(d + 1);
+                    queue.push_back(n);
+                }
+            }
+        }
+        dist
+    }
+
+    // The greatest number of hops from `start` to any node reachable from it.
+    // Nodes which can't be reached don't count.
+    fn eccentricity(&self, start: usize) -> usize {
+        self.distances(start).into_iter().flatten().max().unwrap_or(0)
+    }
+
+    // The fraction of pairs of `i`'s neighbours which are neighbours of each
+    // other, where `neighbors` is the result of `undirected` with duplicates
+    // removed. Zero if `i` has fewer than two neighbours.
+    fn clustering(&self, i: usize, neighbors: &[HashSet<usize>]) -> f64 {
+        let around: Vec<_> = neighbors[i].iter().filter(|&&n| n != i).collect();
+        let k = around.len();
+        if k < 2 {
+            return 0.0;
+        }
+        let mut links = 0;
+        for (j, &a) in around.iter().enumerate() {
+            for &b in &around[j + 1..] {
+                if neighbors[*a].contains(b) {
+                    links += 1;
+                }
+            }
+        }
+        links as f64 / (k * (k - 1) / 2) as f64
+    }
+
+    // Follows `pred` back from `i` to the start node.
+    fn path_to(&self, mut i: usize, pred: &HashMap<usize, usize>) -> Vec<T> {
+        let mut path = vec![self.data[i].clone()];
+        while i != 0 {
+            i = pred[&i];
+            path.push(self.data[i].clone());
+        }
+        path.reverse();
+        path
+    }
+}
+
+// A pre-order, depth-first iterator over the nodes of a graph, see `dfs_iter`.
+struct DfsIter<T> {
+    stack: Vec<Rc<RefCell<Node<T>>>>,
+    seen: HashSet<NodeId<T>>,
+}
+
+impl<T: Clone + Eq + Hash> Iterator for DfsIter<T> {
+    type Item = Rc<RefCell<Node<T>>>;
+
+    fn next(&mut self) -> Option<Rc<RefCell<Node<T>>>> {
+        while let Some(n) = self.stack.pop() {
+            if !self.seen.insert(id(&n)) {
+                continue;
+            }
+            self.stack.extend(n.borrow().neighbors().rev());
+            return Some(n);
+        }
+        None
+    }
+}
+
+impl<T: Clone + Eq + Hash> DfsIter<T> {
+    // The next `n` nodes, or fewer if we run out, so a slow consumer can take
+    // the traversal a piece at a time. Empty once the traversal is finished.
+    fn next_batch(&mut self, n: usize) -> Vec<Rc<RefCell<Node<T>>>> {
+        self.take(n).collect()
+    }
+}
+
+// The start node of a graph, so that `for n in &root` visits every node.
+struct GraphRoot<T = &'static str>(Rc<RefCell<Node<T>>>);
+
+impl<T: Clone + Eq + Hash> IntoIterator for &GraphRoot<T> {
+    type Item = Rc<RefCell<Node<T>>>;
+    type IntoIter = DfsIter<T>;
+
+    fn into_iter(self) -> DfsIter<T> {
+        dfs_iter(&self.0)
+    }
+}
+
+// A graph which holds on to all of its nodes, rather than reaching them from a
+// start node, so it can start out empty and needn't be connected. Use
+// `add_node` and then `add_edge` to build it up from nothing.
+struct NodeSet<T = &'static str> {
+    nodes: Vec<Rc<RefCell<Node<T>>>>,
+}
+
+impl<T> Default for NodeSet<T> {
+    fn default() -> NodeSet<T> {
+        NodeSet { nodes: Vec::new() }
+    }
+}
+
+impl<T: Clone + Eq + Hash> NodeSet<T> {
+    fn add_node(&mut self, datum: T) -> Rc<RefCell<Node<T>>> {
+        let node = Node::isolated(datum);
+        self.nodes.push(node.clone());
+        node
+    }
+
+    // In the order they were added.
+    fn nodes(&self) -> &[Rc<RefCell<Node<T>>>] {
+        &self.nodes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Forgets every node which can't be reached from `root` and clears its
+    // edges. A node can't tell whether anything outside the graph points to it,
+    // so starting from `root` alone we could never find the unreachable ones;
+    // that's why this is on `NodeSet`, which holds on to every node. Clearing
+    // their edges breaks any cycles between them, so once the caller lets go
+    // of them they are freed. Weak edges to them stop upgrading at that point,
+    // so only strong edges are followed to find the reachable nodes.
+    fn prune_unreachable(&mut self, root: &Rc<RefCell<Node<T>>>) {
+        let mut reachable = HashSet::new();
+        let mut stack = vec![root.clone()];
+        while let Some(n) = stack.pop() {
+            if reachable.insert(id(&n)) {
+                stack.extend(n.borrow().edges.iter().cloned());
+            }
+        }
+        for n in &self.nodes {
+            if !reachable.contains(&id(n)) {
+                n.borrow_mut().edges.clear();
+            }
+        }
+        self.nodes.retain(|n| reachable.contains(&id(n)));
+    }
+}
+
+impl<T: Clone + Eq + Hash> GraphRoot<T> {
+    // Every distinct node, in the order `traverse` visits them.
+    fn nodes(&self) -> impl Iterator<Item = Rc<RefCell<Node<T>>>> {
+        dfs_iter(&self.0)
+    }
+
+    // Every edge as a `(from, to)` pair. Each edge is visited once, however
+    // many paths lead to it; parallel edges are reported separately.
+    fn edges(&self) -> impl Iterator<Item = (T, T)> {
+        let adj = self.0.borrow().adjacency();
+        let pairs: Vec<_> = adj.edges.iter()
+            .enumerate()
+            .flat_map(|(from, targets)| targets.iter().map(move |&to| (from, to)))
+            .map(|(from, to)| (adj.data[from].clone(), adj.data[to].clone()))
+            .collect();
+        pairs.into_iter()
+    }
+}
+
+// An immutable copy of a graph, made by `Node::freeze`. There are no `Rc`s or
+// `RefCell`s, just indices, so it can be shared between threads as long as the
+// data can. Nodes are in the order `traverse` visits them, starting with the
+// node it was frozen from.
+struct FrozenGraph<T = &'static str> {
+    data: Vec<T>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl<T> FrozenGraph<T> {
+    // Calls `f` on every node in the same order as `Node::traverse`. The nodes
+    // are stored in that order, so there is no need to follow any edges.
+    fn traverse<F>(&self, f: F)
+        where F: Fn(&T)
+    {
+        for datum in &self.data {
+            f(datum);
+        }
+    }
+
+    // The indices of the nodes `i` has edges to.
+    fn neighbors(&self, i: usize) -> &[usize] {
+        &self.edges[i]
+    }
+
+    fn datum(&self, i: usize) -> &T {
+        &self.data[i]
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+// What changed between two graphs, see `diff`. Nodes are in the order
+// `traverse` visits them in whichever graph they are in.
+#[derive(Debug, PartialEq)]
+struct GraphDiff<T = &'static str> {
+    added_nodes: Vec<T>,
+    removed_nodes: Vec<T>,
+    added_edges: Vec<(T, T)>,
+    removed_edges: Vec<(T, T)>,
+}
+
+impl<T> GraphDiff<T> {
+    fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty() &&
+        self.removed_nodes.is_empty() &&
+        self.added_edges.is_empty() &&
+        self.removed_edges.is_empty()
+    }
+}
+
+// Bookkeeping for Tarjan's algorithm, see `strongly_connected_components`.
+struct Tarjan<'a> {
+    edges: &'a [Vec<usize>],
+    // The order in which nodes were first visited.
+    index: Vec<Option<usize>>,
+    // The smallest index reachable from each node using nodes on `stack`.
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(edges: &'a [Vec<usize>]) -> Tarjan<'a> {
+        Tarjan {
+            edges,
+            index: vec![None; edges.len()],
+            lowlink: vec![0; edges.len()],
+            on_stack: vec![false; edges.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, v: usize) {
+        self.index[v] = Some(self.next_index);
+        self.lowlink[v] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+
+        let edges = self.edges;
+        for &w in &edges[v] {
+            match self.index[w] {
+                None => {
+                    self.visit(w);
+                    self.lowlink[v] = cmp::min(self.lowlink[v], self.lowlink[w]);
+                }
+                Some(i) if self.on_stack[w] => {
+                    self.lowlink[v] = cmp::min(self.lowlink[v], i);
+                }
+                Some(_) => {}
+            }
+        }
+
+        // `v` is the root of a component, which is everything above it on the
+        // stack.
+        if self.index[v] == Some(self.lowlink[v]) {
+            let mut component = Vec::new();
+            loop {
+                let w = self.stack.pop().unwrap();
+                self.on_stack[w] = false;
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+// Bookkeeping for finding cut vertices and bridges, see `articulation_points`
+// and `bridges`.
+struct LowLink<'a> {
+    // For each node, its neighbours in either direction and the index of the
+    // edge which joins them, so that parallel edges can be told apart.
+    neighbors: &'a [Vec<(usize, usize)>],
+    // The order in which nodes were first visited.
+    discovered: Vec<Option<usize>>,
+    // The earliest discovered node reachable from each node's subtree using at
+    // most one edge which isn't in the search tree.
+    low: Vec<usize>,
+    next: usize,
+    points: HashSet<usize>,
+    // Indices of edges.
+    bridges: Vec<usize>,
+}
+
+impl<'a> LowLink<'a> {
+    fn new(neighbors: &'a [Vec<(usize, usize)>]) -> LowLink<'a> {
+        LowLink {
+            neighbors,
+            discovered: vec![None; neighbors.len()],
+            low: vec![0; neighbors.len()],
+            next: 0,
+            points: HashSet::new(),
+            bridges: Vec::new(),
+        }
+    }
+
+    // `from` is the edge we arrived at `v` by.
+    fn visit(&mut self, v: usize, from: Option<usize>) {
+        let d = self.next;
+        self.discovered[v] = Some(d);
+        self.low[v] = d;
+        self.next += 1;
+
+        let mut children = 0;
+        let neighbors = self.neighbors;
+        for &(w, edge) in &neighbors[v] {
+            if Some(edge) == from {
+                continue;
+            }
+            match self.discovered[w] {
+                None => {
+                    children += 1;
+                    self.visit(w, Some(edge));
+                    self.low[v] = cmp::min(self.low[v], self.low[w]);
+                    // Nothing under `w` reaches above `v` without going
+                    // through `v`...
+                    if from.is_some() && self.low[w] >= d {
+                        self.points.insert(v);
+                    }
+                    // ... or even reaches `v` without this edge.
+                    if self.low[w] > d {
+                        self.bridges.push(edge);
+                    }
+                }
+                Some(dw) => self.low[v] = cmp::min(self.low[v], dw),
+            }
+        }
+
+        // The root is a cut vertex if it has subtrees which are only joined
+        // through it.
+        if from.is_none() && children > 1 {
+            self.points.insert(v);
+        }
+    }
+}
+
+// Builds a graph a node or edge at a time, e.g.,
+// `GraphBuilder::new().edge("A", "B").edge("B", "A").build()`. Nodes are
+// created the first time they are mentioned, so `node` is only needed for
+// nodes without any edges.
+struct GraphBuilder<T> {
+    nodes: HashMap<T, Rc<RefCell<Node<T>>>>,
+    first: Option<T>,
+}
+
+impl<T: Clone + Eq + Hash> GraphBuilder<T> {
+    fn new() -> GraphBuilder<T> {
+        GraphBuilder {
+            nodes: HashMap::new(),
+            first: None,
+        }
+    }
+
+    fn node(mut self, datum: T) -> GraphBuilder<T> {
+        self.get(datum);
+        self
+    }
+
+    fn edge(mut self, from: T, to: T) -> GraphBuilder<T> {
+        let from = self.get(from);
+        let to = self.get(to);
+        add_edge(&from, &to);
+        self
+    }
+
+    // Returns the first node mentioned.
+    //
+    // Panics if no nodes were added.
+    fn build(self) -> Rc<RefCell<Node<T>>> {
+        let first = self.first.expect("no nodes to build a graph from");
+        self.nodes[&first].clone()
+    }
+
+    fn get(&mut self, datum: T) -> Rc<RefCell<Node<T>>> {
+        if self.first.is_none() {
+            self.first = Some(datum.clone());
+        }
+        self.nodes.entry(datum.clone()).or_insert_with(|| Node::new(datum)).clone()
+    }
+}
+
+// For each node reachable from some roots, the nodes with edges to it, so that
+// we can search backwards along edges. See `bidirectional_shortest_path`.
+struct ReverseAdjacency<T> {
+    // Indices into `predecessors`.
+    index: HashMap<NodeId<T>, usize>,
+    predecessors: Vec<Vec<Rc<RefCell<Node<T>>>>>,
+}
+
+impl<T: Clone + Eq + Hash> ReverseAdjacency<T> {
+    fn new(roots: &[Rc<RefCell<Node<T>>>]) -> ReverseAdjacency<T> {
+        let mut reverse = ReverseAdjacency {
+            index: HashMap::new(),
+            predecessors: Vec::new(),
+        };
+        let mut seen = HashSet::new();
+        for root in roots {
+            let mut stack = vec![root.clone()];
+            while let Some(n) = stack.pop() {
+                if !seen.insert(id(&n)) {
+                    continue;
+                }
+                for e in &n.borrow().edges {
+                    let next = reverse.predecessors.len();
+                    let i = *reverse.index.entry(id(e)).or_insert(next);
+                    if i == next {
+                        reverse.predecessors.push(Vec::new());
+                    }
+                    reverse.predecessors[i].push(n.clone());
+                    stack.push(e.clone());
+                }
+            }
+        }
+        reverse
+    }
+
+    fn predecessors(&self, node: &Rc<RefCell<Node<T>>>) -> &[Rc<RefCell<Node<T>>>] {
+        self.index.get(&id(node)).map_or(&[], |&i| &self.predecessors[i])
+    }
+}
+
+// The nodes found by one side of a bidirectional search, with their distance
+// from where that side started and the node they were reached from.
+type Visited<T> = HashMap<NodeId<T>, (usize, Option<Rc<RefCell<Node<T>>>>)>;
+
+// The length of a path found by a bidirectional search and the edge where the
+// two sides met.
+type Meeting<T> = (usize, Rc<RefCell<Node<T>>>, Rc<RefCell<Node<T>>>);
+
+// A small pseudo-random number generator (SplitMix64), so that `random_graph`
+// gives the same graph for the same seed without needing a crate for it.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug)]
+struct CycleError;
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "graph contains a cycle")
+    }
+}
+
+impl Error for CycleError {}
+
+// Why `from_edge_list` couldn't read its input.
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    // A line which isn't two labels separated by a comma, with its (1-based)
+    // line number.
+    Malformed(usize, String),
+    // There were no edges, so there is no node to return.
+    Empty,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Malformed(line, ref text) => {
+                write!(f, "line {}: expected `from,to`, found {:?}", line, text)
+            }
+            ParseError::Empty => write!(f, "no edges found"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// The shape of a graph, see `Node::classify` and `classify_from`. The first of
+// these which matches is used:
+//
+// * `Cyclic`: there is a cycle, which includes self-loops.
+// * `Dag`: no cycles, but some node has more than one edge into it (parallel
+//   edges count separately).
+// * `Tree`: exactly one node, the root, has no edges into it and every other
+//   node has exactly one. So there are `n - 1` edges and the root can reach
+//   every node.
+// * `Forest`: several roots, each the root of a separate tree. Everything is
+//   reachable from `self` in `Node::classify`, so only `classify_from` can
+//   find a forest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GraphClass {
+    Tree,
+    Forest,
+    Dag,
+    Cyclic,
+}
+
+// Whether a graph can be drawn without lifting the pen, see `eulerian_kind`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EulerianKind {
+    None,
+    // A walk which uses every edge once, but can't end where it started.
+    Path,
+    // A walk which uses every edge once and ends where it started.
+    Circuit,
+}
+
+// The part an edge plays in a depth-first search, see `classify_edges`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EdgeKind {
+    // The search reached a node for the first time along this edge.
+    Tree,
+    // To an ancestor in the search, or a self-loop. These close cycles.
+    Back,
+    // To a descendant which was already reached along another path.
+    Forward,
+    // To a node in a part of the search which had already finished.
+    Cross,
+}
+
+// Receives events from a depth-first walk, see `Node::accept`. Each node is
+// entered once; everything entered after it is left before it is.
+trait Visitor<T = &'static str> {
+    fn enter(&mut self, datum: T);
+    fn leave(&mut self, datum: T);
+}
+
+impl<T: Clone + Eq + Hash> Node<T> {
+    pub(crate) fn new(datum: T) -> Rc<RefCell<Node<T>>> {
         Rc::new(RefCell::new(Node {
-            datum: datum,
+            datum,
             edges: Vec::new(),
+            weak_edges: Vec::new(),
         }))
     }
 
-    fn traverse<F>(&self, f: &F, seen: &mut HashSet<&'static str>)
-        where F: Fn(&'static str)
+    // The same as `new`, but says what we mean when starting a graph from
+    // scratch.
+    fn isolated(datum: T) -> Rc<RefCell<Node<T>>> {
+        Node::new(datum)
+    }
+
+    fn traverse<F>(&self, f: &F, seen: &mut HashSet<NodeId<T>>)
+        where F: Fn(&T)
     {
-        if seen.contains(&self.datum) {
+        if seen.contains(&(self as NodeId<T>)) {
             return;
         }
-        f(self.datum);
-        seen.insert(self.datum);
-        for n in &self.edges {
+        f(&self.datum);
+        seen.insert(self);
+        for n in self.neighbors() {
             n.borrow().traverse(f, seen);
         }
     }
 
-    fn first(&self) -> Rc<RefCell<Node>> {
-        self.edges[0].clone()
-    }
+    // Like `traverse`, but keeps an explicit stack rather than recursing, so
+    // deep graphs can't overflow the call stack.
+    fn traverse_iter<F>(&self, f: &F)
+        where F: Fn(&T)
+    {
+        let mut seen = HashSet::new();
+        f(&self.datum);
+        seen.insert(self as NodeId<T>);
+        // Children are pushed in reverse so they are popped in order.
+        let mut stack: Vec<_> = self.neighbors().rev().collect();
+        while let Some(n) = stack.pop() {
+            if !seen.insert(id(&n)) {
+                continue;
+            }
+            let n = n.borrow();
+            f(&n.datum);
+            stack.extend(n.neighbors().rev());
+        }
+    }
+
+    // Like `traverse_iter`, but stops as soon as `f` returns `Break`. Returns
+    // `Break` if the walk was stopped early.
+    fn traverse_until<F>(&self, f: &mut F) -> ControlFlow<()>
+        where F: FnMut(&T) -> ControlFlow<()>
+    {
+        let mut seen = HashSet::new();
+        f(&self.datum)?;
+        seen.insert(self as NodeId<T>);
+        let mut stack: Vec<_> = self.neighbors().rev().collect();
+        while let Some(n) = stack.pop() {
+            if !seen.insert(id(&n)) {
+                continue;
+            }
+            let n = n.borrow();
+            f(&n.datum)?;
+            stack.extend(n.neighbors().rev());
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn traverse_bfs<F>(&self, f: &F)
+        where F: Fn(&T)
+    {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        f(&self.datum);
+        seen.insert(self as NodeId<T>);
+        queue.extend(self.neighbors());
+        while let Some(n) = queue.pop_front() {
+            if !seen.insert(id(&n)) {
+                continue;
+            }
+            let n = n.borrow();
+            f(&n.datum);
+            queue.extend(n.neighbors());
+        }
+    }
+
+    // Like `traverse_bfs`, but also passes each node's distance from `self`.
+    // Nodes come off the queue in order of depth, so the first time we see a
+    // node is by a shortest route.
+    fn traverse_levels<F>(&self, f: &F)
+        where F: Fn(&T, usize)
+    {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        f(&self.datum, 0);
+        seen.insert(self as NodeId<T>);
+        queue.extend(self.neighbors().map(|n| (n, 1)));
+        while let Some((n, depth)) = queue.pop_front() {
+            if !seen.insert(id(&n)) {
+                continue;
+            }
+            let n = n.borrow();
+            f(&n.datum, depth);
+            queue.extend(n.neighbors().map(|n| (n, depth + 1)));
+        }
+    }
+
+    // The discovery and finish time of each node in a depth-first search from
+    // `self`. If `a` is reachable from `b` through the search, `a`'s times lie
+    // between `b`'s. Keyed by datum, so nodes should have distinct data.
+    fn dfs_timestamps(&self) -> HashMap<T, (usize, usize)> {
+        let adj = self.adjacency();
+        let times = adj.timestamps();
+        adj.data.into_iter().zip(times).collect()
+    }
+
+    // Classifies every reachable edge by comparing the timestamps of its ends.
+    // If the target's times lie inside the source's, the target is a
+    // descendant and the edge is a tree or forward edge; if the other way
+    // round, it is a back edge; if they don't overlap, it is a cross edge.
+    fn classify_edges(&self) -> Vec<((T, T), EdgeKind)> {
+        let adj = self.adjacency();
+        let times = adj.timestamps();
+        let descends = |a: usize, b: usize| times[b].0 < times[a].0 && times[a].1 < times[b].1;
+
+        // Each node was reached from the latest-discovered node which points
+        // to it and was still being searched at the time.
+        let mut parent: Vec<Option<usize>> = vec![None; adj.data.len()];
+        for (from, targets) in adj.edges.iter().enumerate() {
+            for &to in targets {
+                if descends(to, from) && parent[to].is_none_or(|p| times[p].0 < times[from].0) {
+                    parent[to] = Some(from);
+                }
+            }
+        }
+
+        let mut reached = vec![false; adj.data.len()];
+        let mut kinds = Vec::new();
+        for (from, targets) in adj.edges.iter().enumerate() {
+            for &to in targets {
+                // Only the first of several parallel edges is a tree edge.
+                let kind = if parent[to] == Some(from) && !reached[to] {
+                    reached[to] = true;
+                    EdgeKind::Tree
+                } else if descends(to, from) {
+                    EdgeKind::Forward
+                } else if from == to || descends(from, to) {
+                    EdgeKind::Back
+                } else {
+                    EdgeKind::Cross
+                };
+                kinds.push(((adj.data[from].clone(), adj.data[to].clone()), kind));
+            }
+        }
+        kinds
+    }
+
+    // Walks the graph depth-first, calling `enter` on the way down to each
+    // node and `leave` on the way back up. An edge back to a node which has
+    // been entered but not left yet is skipped, like any other seen node.
+    fn accept<V: Visitor<T>>(&self, v: &mut V) {
+        self.walk(v, &mut HashSet::new());
+    }
+
+    fn walk<V: Visitor<T>>(&self, v: &mut V, seen: &mut HashSet<NodeId<T>>) {
+        if !seen.insert(self) {
+            return;
+        }
+        v.enter(self.datum.clone());
+        for n in self.neighbors() {
+            n.borrow().walk(v, seen);
+        }
+        v.leave(self.datum.clone());
+    }
+
+    // Visits each node after all of its children. A node is marked as seen
+    // before its children are visited, so on a cycle the node which closes the
+    // cycle is visited before the node it points back to.
+    fn traverse_postorder<F>(&self, f: &F)
+        where F: Fn(&T)
+    {
+        self.postorder(f, &mut HashSet::new());
+    }
+
+    fn postorder<F>(&self, f: &F, seen: &mut HashSet<NodeId<T>>)
+        where F: Fn(&T)
+    {
+        if !seen.insert(self) {
+            return;
+        }
+        for n in self.neighbors() {
+            n.borrow().postorder(f, seen);
+        }
+        f(&self.datum);
+    }
+
+    // Visits the nodes at most `max_depth` edges from `self`, passing each
+    // node's depth to `f`. Each node is visited once, at the depth the
+    // depth-first walk first reaches it, which may be more than its shortest
+    // distance from `self`.
+    fn traverse_depth_limited<F>(&self, f: &F, max_depth: usize)
+        where F: Fn(&T, usize)
+    {
+        self.depth_limited(f, 0, max_depth, &mut HashMap::new());
+    }
+
+    // `depths` records the shallowest depth each node has been expanded at. If
+    // we reach a node again by a shorter path we expand it again (without
+    // calling `f`), otherwise nodes within the limit could be missed.
+    fn depth_limited<F>(&self,
+                        f: &F,
+                        depth: usize,
+                        max_depth: usize,
+                        depths: &mut HashMap<NodeId<T>, usize>)
+        where F: Fn(&T, usize)
+    {
+        match depths.entry(self) {
+            Entry::Occupied(mut e) => {
+                if *e.get() <= depth {
+                    return;
+                }
+                e.insert(depth);
+            }
+            Entry::Vacant(e) => {
+                e.insert(depth);
+                f(&self.datum, depth);
+            }
+        }
+        if depth == max_depth {
+            return;
+        }
+        for n in self.neighbors() {
+            n.borrow().depth_limited(f, depth + 1, max_depth, depths);
+        }
+    }
+
+    // Iterative deepening: depth-first searches for `target` with limits of
+    // 0, 1, 2, ... up to `max_depth`, returning the limit of the first search
+    // to find it, which is its distance from `self`. Nothing is remembered
+    // between or during searches, so memory use is proportional to the depth
+    // rather than the size of the graph; the cost is that nodes near `self`
+    // are explored again by every search, and by every path to them.
+    fn iddfs(&self, target: T, max_depth: usize) -> Option<usize> {
+        (0..=max_depth).find(|&limit| self.within(&target, limit))
+    }
+
+    // Whether `target` is at most `limit` edges from `self`.
+    fn within(&self, target: &T, limit: usize) -> bool {
+        if self.datum == *target {
+            return true;
+        }
+        limit > 0 && self.neighbors().any(|n| n.borrow().within(target, limit - 1))
+    }
+
+    // The targets of `edges`, then those of `weak_edges` which are still
+    // alive. Cloning an `Rc` just bumps its reference count, so this is cheap.
+    fn neighbors(&self) -> impl DoubleEndedIterator<Item = Rc<RefCell<Node<T>>>> + '_ {
+        self.edges.iter().cloned().chain(self.weak_edges.iter().filter_map(Weak::upgrade))
+    }
+
+    fn node_count(&self) -> usize {
+        self.reachable_count()
+    }
+
+    // The number of distinct nodes reachable from `self`, including `self`.
+    // Nodes are told apart by address, so only a pointer is hashed per node
+    // and nodes with the same datum are still counted separately. Uses an
+    // explicit stack, so it is fine for large graphs.
+    fn reachable_count(&self) -> usize {
+        let mut seen = HashSet::new();
+        seen.insert(self as NodeId<T>);
+        let mut stack: Vec<_> = self.neighbors().collect();
+        while let Some(n) = stack.pop() {
+            if seen.insert(id(&n)) {
+                stack.extend(n.borrow().neighbors());
+            }
+        }
+        seen.len()
+    }
+
+    fn edge_count(&self) -> usize {
+        self.count_edges(&mut HashSet::new())
+    }
+
+    // The fraction of possible edges between the nodes reachable from `self`
+    // which are present, treating edges as directed. Self-loops and parallel
+    // edges are counted like any other edge, so this can exceed one. Zero if
+    // there are fewer than two nodes.
+    fn density(&self) -> f64 {
+        let nodes = self.node_count();
+        if nodes < 2 {
+            return 0.0;
+        }
+        self.edge_count() as f64 / (nodes * (nodes - 1)) as f64
+    }
+
+    // The local clustering coefficient of the node labelled `datum`: how close
+    // its neighbours are to all being connected to each other, between zero
+    // and one. Edges count in either direction and self-loops are ignored.
+    // Zero if the node has fewer than two neighbours or can't be found.
+    fn local_clustering(&self, datum: T) -> f64 {
+        let adj = self.adjacency();
+        let Some(i) = adj.data.iter().position(|d| *d == datum) else {
+            return 0.0;
+        };
+        let neighbors: Vec<HashSet<_>> =
+            adj.undirected().into_iter().map(|n| n.into_iter().collect()).collect();
+        adj.clustering(i, &neighbors)
+    }
+
+    // The average of `local_clustering` over every node reachable from `self`.
+    // Nodes with fewer than two neighbours count as zero rather than being
+    // left out.
+    fn global_clustering(&self) -> f64 {
+        let adj = self.adjacency();
+        let neighbors: Vec<HashSet<_>> =
+            adj.undirected().into_iter().map(|n| n.into_iter().collect()).collect();
+        let total: f64 = (0..adj.data.len()).map(|i| adj.clustering(i, &neighbors)).sum();
+        total / adj.data.len() as f64
+    }
+
+    fn count_edges(&self, seen: &mut HashSet<NodeId<T>>) -> usize {
+        if !seen.insert(self) {
+            return 0;
+        }
+        let mut count = 0;
+        for n in self.neighbors() {
+            count += 1 + n.borrow().count_edges(seen);
+        }
+        count
+    }
+
+    // A rough idea of how much memory the graph reachable from `self` uses:
+    // each node plus the spare capacity as well as the used part of its edge
+    // lists. The counts `Rc` keeps next to each node, the `RefCell`'s borrow
+    // flag, and anything the data point to are left out, so the real figure
+    // is somewhat higher, but this is good enough for comparing graphs.
+    fn estimated_bytes(&self) -> usize {
+        self.count_bytes(&mut HashSet::new())
+    }
+
+    fn count_bytes(&self, seen: &mut HashSet<NodeId<T>>) -> usize {
+        if !seen.insert(self) {
+            return 0;
+        }
+        let mut bytes = mem::size_of::<Node<T>>() +
+                        self.edges.capacity() * mem::size_of::<Rc<RefCell<Node<T>>>>() +
+                        self.weak_edges.capacity() * mem::size_of::<Weak<RefCell<Node<T>>>>();
+        for n in &self.edges {
+            bytes += n.borrow().count_bytes(seen);
+        }
+        bytes
+    }
+
+    fn out_degree(&self) -> usize {
+        self.neighbors().count()
+    }
+
+    // The number of edges in the reachable graph which point at the node
+    // labelled `datum`.
+    fn in_degree(&self, datum: T) -> usize {
+        let adj = self.adjacency();
+        adj.edges.iter().flatten().filter(|&&t| adj.data[t] == datum).count()
+    }
+
+    // In-degree plus out-degree of the node labelled `datum`, 0 if there is no
+    // such node reachable from `self`.
+    fn degree(&self, datum: T) -> usize {
+        let adj = self.adjacency();
+        let out = adj.data.iter().position(|d| *d == datum).map_or(0, |i| adj.edges[i].len());
+        out + self.in_degree(datum)
+    }
+
+    // Only strong edges count, so this is false once `break_cycles` has run:
+    // whatever is left can be freed by reference counting.
+    fn has_cycle(&self) -> bool {
+        self.find_cycle(&mut HashSet::new(), &mut HashSet::new())
+    }
+
+    // `path` holds the nodes on the current DFS path, reaching one of those
+    // again means we have followed a back-edge.
+    fn find_cycle(&self, seen: &mut HashSet<NodeId<T>>, path: &mut HashSet<NodeId<T>>) -> bool {
+        let key = self as NodeId<T>;
+        if path.contains(&key) {
+            return true;
+        }
+        if !seen.insert(key) {
+            return false;
+        }
+        path.insert(key);
+        for n in &self.edges {
+            if n.borrow().find_cycle(seen, path) {
+                return true;
+            }
+        }
+        path.remove(&key);
+        false
+    }
+
+    fn topological_sort(&self) -> Result<Vec<T>, CycleError> {
+        let adj = self.adjacency();
+        let order = adj.topological_order().ok_or(CycleError)?;
+        Ok(order.into_iter().map(|i| adj.data[i].clone()).collect())
+    }
+
+    // The number of distinct paths from `self` to nodes labelled `target`,
+    // without listing them. Working backwards through a topological order,
+    // each node's count is the sum of its successors' counts, so each is
+    // worked out once. Errs if there is a cycle reachable from `self`, even
+    // one which doesn't lead to `target`. The count saturates at `u64::MAX`.
+    fn count_paths(&self, target: T) -> Result<u64, CycleError> {
+        let adj = self.adjacency();
+        let order = adj.topological_order().ok_or(CycleError)?;
+        let mut counts = vec![0; adj.data.len()];
+        for &i in order.iter().rev() {
+            let mut count: u64 = if adj.data[i] == target { 1 } else { 0 };
+            for &t in &adj.edges[i] {
+                count = count.saturating_add(counts[t]);
+            }
+            counts[i] = count;
+        }
+        Ok(counts[0])
+    }
+
+    // See `GraphClass` for how graphs are classified.
+    fn classify(&self) -> GraphClass {
+        self.adjacency().classify()
+    }
+
+    // Tarjan's algorithm. Components are returned in reverse topological
+    // order, i.e., no component has an edge to a later one.
+    fn strongly_connected_components(&self) -> Vec<Vec<T>> {
+        let adj = self.adjacency();
+        let mut tarjan = Tarjan::new(&adj.edges);
+        tarjan.visit(0);
+        tarjan.components.iter()
+            .map(|c| c.iter().map(|&i| adj.data[i].clone()).collect())
+            .collect()
+    }
+
+    // Kosaraju's algorithm, for comparison with Tarjan's. Any node which can
+    // reach the node which finishes last in a depth-first search is in its
+    // component, so we search the transposed graph from that node, then from
+    // the last to finish of the remaining nodes, and so on. Components are
+    // returned in topological order, the reverse of
+    // `strongly_connected_components`.
+    fn scc_kosaraju(&self) -> Vec<Vec<T>> {
+        let adj = self.adjacency();
+        let mut order = Vec::new();
+        adj.finish_order(0, &mut vec![false; adj.data.len()], &mut order);
+
+        let transposed = self.transpose();
+        let mut seen = HashSet::new();
+        let mut components = Vec::new();
+        for &i in order.iter().rev() {
+            if !seen.insert(id(&transposed[i])) {
+                continue;
+            }
+            let mut component = Vec::new();
+            let mut stack = vec![transposed[i].clone()];
+            while let Some(n) = stack.pop() {
+                let n = n.borrow();
+                component.push(n.datum.clone());
+                for e in &n.edges {
+                    if seen.insert(id(e)) {
+                        stack.push(e.clone());
+                    }
+                }
+            }
+            components.push(component);
+        }
+
+        // The transposed graph has cycles wherever the original does, so we
+        // must break them for it to be freed.
+        for n in &transposed {
+            n.borrow_mut().edges.clear();
+        }
+        components
+    }
+
+    // The nodes whose removal would split the graph reachable from `self` into
+    // more pieces, ignoring the direction of edges.
+    fn articulation_points(&self) -> HashSet<T> {
+        let adj = self.adjacency();
+        let neighbors = adj.undirected_edges();
+        let mut low_link = LowLink::new(&neighbors);
+        low_link.visit(0, None);
+        low_link.points.iter().map(|&i| adj.data[i].clone()).collect()
+    }
+
+    // The edges whose removal would split the graph reachable from `self`
+    // into more pieces, ignoring the direction of edges. Edges are given in
+    // their original direction.
+    fn bridges(&self) -> Vec<(T, T)> {
+        let adj = self.adjacency();
+        let neighbors = adj.undirected_edges();
+        let mut low_link = LowLink::new(&neighbors);
+        low_link.visit(0, None);
+        let edges: Vec<_> = adj.edges.iter()
+            .enumerate()
+            .flat_map(|(from, targets)| targets.iter().map(move |&to| (from, to)))
+            .collect();
+        low_link.bridges.iter()
+            .map(|&e| (adj.data[edges[e].0].clone(), adj.data[edges[e].1].clone()))
+            .collect()
+    }
+
+    // Returns the path with the fewest edges from `self` to `target`,
+    // including both ends.
+    fn shortest_path(&self, target: T) -> Option<Vec<T>> {
+        let adj = self.adjacency();
+        // Maps each discovered node to the node we first reached it from.
+        let mut pred = HashMap::new();
+        let mut queue = VecDeque::new();
+        pred.insert(0, 0);
+        queue.push_back(0);
+        while let Some(i) = queue.pop_front() {
+            if adj.data[i] == target {
+                return Some(adj.path_to(i, &pred));
+            }
+            for &t in &adj.edges[i] {
+                if let Entry::Vacant(e) = pred.entry(t) {
+                    e.insert(i);
+                    queue.push_back(t);
+                }
+            }
+        }
+        None
+    }
+
+    // Whether the nodes reachable from `self` can be split into two groups so
+    // every edge goes between the groups, ignoring direction. The sample graph
+    // is: `A`, `E` and `F` on one side and `B`, `C` and `D` on the other.
+    fn is_bipartite(&self) -> bool {
+        let adj = self.adjacency();
+        let neighbors = adj.undirected();
+        let mut side = vec![None; adj.data.len()];
+        side[0] = Some(false);
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+        while let Some(i) = queue.pop_front() {
+            let s = side[i].unwrap();
+            for &n in &neighbors[i] {
+                match side[n] {
+                    None => {
+                        side[n] = Some(!s);
+                        queue.push_back(n);
+                    }
+                    Some(t) if t == s => return false,
+                    Some(_) => {}
+                }
+            }
+        }
+        true
+    }
+
+    // The number of outgoing edges of each node reachable from `self`. Nodes
+    // are identified by datum here, so nodes which share a datum have their
+    // degrees added together.
+    fn degree_centrality(&self) -> HashMap<T, usize> {
+        let adj = self.adjacency();
+        let mut degrees = HashMap::new();
+        for (datum, targets) in adj.data.into_iter().zip(&adj.edges) {
+            *degrees.entry(datum).or_insert(0) += targets.len();
+        }
+        degrees
+    }
+
+    // For each node reachable from `self`, one over the sum of its distances
+    // to the nodes it can reach. Nodes it can't reach are left out of the sum
+    // rather than counting as infinitely far away, so a node which can't reach
+    // anything gets zero. As with `degree_centrality`, nodes which share a
+    // datum have their scores added together.
+    fn closeness_centrality(&self) -> HashMap<T, f64> {
+        let adj = self.adjacency();
+        let mut closeness = HashMap::new();
+        for (i, datum) in adj.data.iter().enumerate() {
+            let total: usize = adj.distances(i).into_iter().flatten().sum();
+            let c = if total == 0 { 0.0 } else { 1.0 / total as f64 };
+            *closeness.entry(datum.clone()).or_insert(0.0) += c;
+        }
+        closeness
+    }
+
+    // Ranks the nodes reachable from `self` by the power method: each round,
+    // every node shares its rank between its out-edges, and nodes with no
+    // out-edges share theirs between every node. `damping` is the chance of
+    // following an edge rather than jumping to a random node. The ranks sum to
+    // one; nodes which share a datum have their ranks added together.
+    fn pagerank(&self, damping: f64, iterations: usize) -> HashMap<T, f64> {
+        let adj = self.adjacency();
+        let n = adj.data.len() as f64;
+        let mut rank = vec![1.0 / n; adj.data.len()];
+        for _ in 0..iterations {
+            let dangling: f64 = adj.edges.iter()
+                .zip(&rank)
+                .filter(|(targets, _)| targets.is_empty())
+                .map(|(_, r)| r)
+                .sum();
+            let base = (1.0 - damping) / n + damping * dangling / n;
+            let mut next = vec![base; adj.data.len()];
+            for (from, targets) in adj.edges.iter().enumerate() {
+                let share = damping * rank[from] / targets.len() as f64;
+                for &to in targets {
+                    next[to] += share;
+                }
+            }
+            rank = next;
+        }
+
+        let mut ranks = HashMap::new();
+        for (datum, r) in adj.data.into_iter().zip(rank) {
+            *ranks.entry(datum).or_insert(0.0) += r;
+        }
+        ranks
+    }
+
+    // Brandes' algorithm: for each node, the number of shortest paths between
+    // other pairs of nodes which go through it. Where a pair has several
+    // shortest paths, each counts for its share, so a node on one of two gets
+    // a half. Every reachable node is used as a source, with a breadth-first
+    // search from each. The values aren't normalised; divide by `(n - 1) *
+    // (n - 2)`, the number of pairs not including a given node, to compare
+    // graphs with different numbers of nodes `n`. Nodes which share a datum
+    // have their values added together.
+    fn betweenness_centrality(&self) -> HashMap<T, f64> {
+        let adj = self.adjacency();
+        let n = adj.data.len();
+        let mut centrality = vec![0.0; n];
+        for source in 0..n {
+            // `paths[i]` counts the shortest paths from `source` to `i`, and
+            // `preds[i]` the nodes just before `i` on them.
+            let mut dist = vec![None; n];
+            let mut paths = vec![0.0; n];
+            let mut preds = vec![Vec::new(); n];
+            let mut order = Vec::new();
+            let mut queue = VecDeque::new();
+            dist[source] = Some(0);
+            paths[source] = 1.0;
+            queue.push_back(source);
+            while let Some(i) = queue.pop_front() {
+                order.push(i);
+                let next = dist[i].unwrap() + 1;
+                for &t in &adj.edges[i] {
+                    if dist[t].is_none() {
+                        dist[t] = Some(next);
+                        queue.push_back(t);
+                    }
+                    if dist[t] == Some(next) {
+                        paths[t] += paths[i];
+                        preds[t].push(i);
+                    }
+                }
+            }
+
+            // Work back from the furthest nodes, passing each node's share of
+            // the paths through it on to its predecessors.
+            let mut dependency = vec![0.0; n];
+            while let Some(i) = order.pop() {
+                for &p in &preds[i] {
+                    dependency[p] += paths[p] / paths[i] * (1.0 + dependency[i]);
+                }
+                if i != source {
+                    centrality[i] += dependency[i];
+                }
+            }
+        }
+
+        let mut betweenness = HashMap::new();
+        for (datum, c) in adj.data.into_iter().zip(centrality) {
+            *betweenness.entry(datum).or_insert(0.0) += c;
+        }
+        betweenness
+    }
+
+    // Colours the nodes reachable from `self` so that nodes which share an
+    // edge, in either direction, get different colours (`0`, `1`, ...). Nodes
+    // are taken in order of decreasing degree, each getting the smallest colour
+    // its neighbours haven't already used. This isn't always the fewest
+    // colours possible, but is quick and usually not far off. Self-loops are
+    // ignored, since no colouring could satisfy them.
+    fn greedy_coloring(&self) -> HashMap<T, usize> {
+        let adj = self.adjacency();
+        let neighbors: Vec<HashSet<usize>> = adj.undirected().into_iter()
+            .enumerate()
+            .map(|(i, ns)| ns.into_iter().filter(|&n| n != i).collect())
+            .collect();
+        let mut order: Vec<_> = (0..adj.data.len()).collect();
+        order.sort_by_key(|&i| cmp::Reverse(neighbors[i].len()));
+
+        let mut colors = vec![None; adj.data.len()];
+        for i in order {
+            let used: HashSet<_> = neighbors[i].iter().filter_map(|&n| colors[n]).collect();
+            colors[i] = (0..).find(|c| !used.contains(c));
+        }
+        adj.data.into_iter().zip(colors.into_iter().map(Option::unwrap)).collect()
+    }
+
+    // The greatest shortest-path distance, in hops, from `self` to any node it
+    // can reach.
+    fn eccentricity(&self) -> usize {
+        self.adjacency().eccentricity(0)
+    }
+
+    // Whether there is a walk using every edge reachable from `self` exactly
+    // once. For a directed graph that depends only on the in- and out-degree of
+    // each node, as long as the nodes with edges are connected, which they are
+    // here since they are all reachable from `self`.
+    fn eulerian_kind(&self) -> EulerianKind {
+        let adj = self.adjacency();
+        // Out-degree minus in-degree.
+        let mut balance = vec![0isize; adj.data.len()];
+        for (from, targets) in adj.edges.iter().enumerate() {
+            balance[from] += targets.len() as isize;
+            for &to in targets {
+                balance[to] -= 1;
+            }
+        }
+        let starts = balance.iter().filter(|&&b| b == 1).count();
+        let ends = balance.iter().filter(|&&b| b == -1).count();
+        let unbalanced = balance.iter().filter(|&&b| b != 0).count();
+        match (unbalanced, starts, ends) {
+            (0, _, _) => EulerianKind::Circuit,
+            (2, 1, 1) => EulerianKind::Path,
+            _ => EulerianKind::None,
+        }
+    }
+
+    // A path which visits every node reachable from `self` exactly once,
+    // starting anywhere. This tries every possibility in the worst case, which
+    // takes exponential time, so it is only suitable for small graphs.
+    fn hamiltonian_path(&self) -> Option<Vec<T>> {
+        let adj = self.adjacency();
+        for start in 0..adj.data.len() {
+            let mut on_path = vec![false; adj.data.len()];
+            on_path[start] = true;
+            let mut path = vec![start];
+            if adj.extend_hamiltonian(&mut path, &mut on_path) {
+                return Some(path.iter().map(|&i| adj.data[i].clone()).collect());
+            }
+        }
+        None
+    }
+
+    // Every path from `self` to `target` which doesn't visit any node twice.
+    // The number of paths can grow exponentially, so this is only suitable for
+    // small graphs.
+    fn all_paths(&self, target: T) -> Vec<Vec<T>> {
+        let adj = self.adjacency();
+        let mut on_path = vec![false; adj.data.len()];
+        on_path[0] = true;
+        let mut paths = Vec::new();
+        adj.simple_paths(&target, &mut vec![0], &mut on_path, &mut paths);
+        paths
+    }
+
+    // The graph as a dense matrix: `m[i][j]` is true when there is an edge from
+    // the `i`th node to the `j`th. Nodes are in the order `traverse` visits
+    // them, with labels in the first vector.
+    fn to_adjacency_matrix(&self) -> (Vec<T>, Vec<Vec<bool>>) {
+        let adj = self.adjacency();
+        let n = adj.data.len();
+        let matrix = adj.edges.iter()
+            .map(|targets| {
+                let mut row = vec![false; n];
+                for &t in targets {
+                    row[t] = true;
+                }
+                row
+            })
+            .collect();
+        (adj.data, matrix)
+    }
+
+    // Copies every reachable node into a new, independent graph with the same
+    // shape, including any sharing and cycles. Returns the copy of `self`.
+    fn deep_clone(&self) -> Rc<RefCell<Node<T>>> {
+        self.adjacency().build().swap_remove(0)
+    }
+
+    // Whether the graphs reachable from `self` and `other` have the same shape
+    // and labels. Nodes are paired up as they are reached, which takes care of
+    // cycles and sharing; paired nodes must have the same datum, and edges to
+    // the same labels in any order. Edges to equally labelled nodes which
+    // haven't been paired yet are paired in the order they are found.
+    fn structurally_eq(&self, other: &Node<T>) -> bool {
+        let a = self.adjacency();
+        let b = other.adjacency();
+        if a.data.len() != b.data.len() {
+            return false;
+        }
+        // `pair[i]` is the node in `b` paired with node `i` in `a`.
+        let mut pair = vec![None; a.data.len()];
+        let mut paired = vec![false; b.data.len()];
+        pair[0] = Some(0);
+        paired[0] = true;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let j = pair[i].unwrap();
+            if a.data[i] != b.data[j] || a.edges[i].len() != b.edges[j].len() {
+                return false;
+            }
+            let mut unmatched = b.edges[j].clone();
+            for &to in &a.edges[i] {
+                let found = match pair[to] {
+                    Some(partner) => unmatched.iter().position(|&u| u == partner),
+                    None => unmatched.iter().position(|&u| !paired[u] && b.data[u] == a.data[to]),
+                };
+                let Some(k) = found else {
+                    return false;
+                };
+                let u = unmatched.swap_remove(k);
+                if pair[to].is_none() {
+                    pair[to] = Some(u);
+                    paired[u] = true;
+                    stack.push(to);
+                }
+            }
+        }
+        true
+    }
+
+    // A hash of the labels and edges of the reachable graph, so that graphs
+    // which are `structurally_eq` hash the same. Edges are hashed as
+    // `(from, to)` label pairs, without regard to order; data can't be sorted
+    // in general, so we sort their hashes instead.
+    fn structural_hash(&self) -> u64 {
+        fn hash_of<H: Hash>(value: H) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let adj = self.adjacency();
+        let mut nodes: Vec<_> = adj.data.iter().map(hash_of).collect();
+        let mut edges: Vec<_> = adj.edges.iter()
+            .enumerate()
+            .flat_map(|(from, targets)| targets.iter().map(move |&to| (from, to)))
+            .map(|(from, to)| hash_of((&adj.data[from], &adj.data[to])))
+            .collect();
+        nodes.sort();
+        edges.sort();
+        hash_of((nodes, edges))
+    }
+
+    // Like `deep_clone`, but passing each datum through `f`. Nodes are matched
+    // up by identity rather than by datum, so `f` doesn't need to give distinct
+    // nodes distinct data.
+    fn map<U, F>(&self, f: F) -> Rc<RefCell<Node<U>>>
+        where U: Clone + Eq + Hash,
+              F: Fn(&T) -> U
+    {
+        let adj = self.adjacency();
+        let mapped = Adjacency {
+            data: adj.data.iter().map(f).collect(),
+            edges: adj.edges,
+        };
+        mapped.build().swap_remove(0)
+    }
+
+    // Copies the reachable graph into a `FrozenGraph`, ready to share.
+    fn freeze(&self) -> Arc<FrozenGraph<T>> {
+        let adj = self.adjacency();
+        Arc::new(FrozenGraph { data: adj.data, edges: adj.edges })
+    }
+
+    // Copies just the nodes for which `keep` returns true, with the edges
+    // between them, and returns the copy of `self`, or `None` if `self` isn't
+    // kept. Kept nodes which could only be reached through nodes which
+    // weren't kept are unreachable in the copy, so are dropped.
+    fn subgraph<P>(&self, keep: P) -> Option<Rc<RefCell<Node<T>>>>
+        where P: Fn(&T) -> bool
+    {
+        let adj = self.adjacency();
+        if !keep(&adj.data[0]) {
+            return None;
+        }
+        // Where each kept node goes in the new graph.
+        let mut index = vec![None; adj.data.len()];
+        let mut kept = Adjacency::empty();
+        for (i, datum) in adj.data.iter().enumerate() {
+            if keep(datum) {
+                index[i] = Some(kept.data.len());
+                kept.data.push(datum.clone());
+            }
+        }
+        for (i, targets) in adj.edges.iter().enumerate() {
+            if index[i].is_some() {
+                kept.edges.push(targets.iter().filter_map(|&t| index[t]).collect());
+            }
+        }
+        Some(kept.build().swap_remove(0))
+    }
+
+    // Copies the reachable graph without its redundant edges: `A -> C` goes if
+    // some other edge out of `A` leads to a path to `C`. Duplicate edges are
+    // merged too. For a DAG this gives the smallest graph with the same
+    // reachability. That isn't well defined if there are cycles; edges are
+    // removed by the same rule, so `A -> B` and `A -> C` both go if `B` and
+    // `C` are on a cycle together, and reachability isn't preserved. Use
+    // `classify` first if the graph might be cyclic.
+    fn transitive_reduction(&self) -> Rc<RefCell<Node<T>>> {
+        let adj = self.adjacency();
+        let mut reduced = Adjacency { data: adj.data.clone(), edges: Vec::new() };
+        for targets in &adj.edges {
+            let mut direct = targets.clone();
+            direct.sort();
+            direct.dedup();
+            let reach: Vec<_> = direct.iter().map(|&t| adj.distances(t)).collect();
+            let kept = direct.iter()
+                .filter(|&&t| !direct.iter().zip(&reach).any(|(&w, r)| w != t && r[t].is_some()))
+                .cloned()
+                .collect();
+            reduced.edges.push(kept);
+        }
+        reduced.build().swap_remove(0)
+    }
+
+    // Copies the reachable graph with an edge from each node straight to each
+    // node reachable from it. A node only gets an edge to itself if it is on a
+    // cycle, so every node on a cycle ends up with an edge to every node on
+    // it, including itself.
+    fn transitive_closure(&self) -> Rc<RefCell<Node<T>>> {
+        let adj = self.adjacency();
+        let mut closure = Adjacency { data: adj.data.clone(), edges: Vec::new() };
+        for (i, targets) in adj.edges.iter().enumerate() {
+            let dist = adj.distances(i);
+            let on_cycle = targets.iter().any(|&t| adj.distances(t)[i].is_some());
+            closure.edges.push((0..adj.data.len())
+                .filter(|&j| if j == i { on_cycle } else { dist[j].is_some() })
+                .collect());
+        }
+        closure.build().swap_remove(0)
+    }
+
+    // Copies the reachable graph with every edge reversed. Most nodes are no
+    // longer reachable from the copy of `self`, so this returns every node, in
+    // the order `traverse` visits them in the original. The copy of `self` is
+    // first.
+    fn transpose(&self) -> Vec<Rc<RefCell<Node<T>>>> {
+        self.adjacency().reversed().build()
+    }
+
+    fn adjacency(&self) -> Adjacency<T> {
+        let mut adj = Adjacency::empty();
+        self.collect(&mut HashMap::new(), &mut adj);
+        adj
+    }
+
+    // Adds `self` and everything reachable from it to `adj`, returning the
+    // index of `self`. `index` holds the nodes already in `adj`. Uses an
+    // explicit stack, like `traverse_iter`, so long chains can't overflow.
+    fn collect(&self, index: &mut HashMap<NodeId<T>, usize>, adj: &mut Adjacency<T>) -> usize {
+        if let Some(&i) = index.get(&(self as NodeId<T>)) {
+            return i;
+        }
+        let start = adj.data.len();
+        index.insert(self, start);
+        adj.data.push(self.datum.clone());
+        adj.edges.push(Vec::new());
+        // Each entry is an edge `(from, to)` still to be added. Children are
+        // pushed in reverse so they are numbered in pre-order.
+        let mut stack: Vec<_> = self.neighbors().rev().map(|n| (start, n)).collect();
+        while let Some((from, n)) = stack.pop() {
+            let to = match index.get(&id(&n)) {
+                Some(&to) => to,
+                None => {
+                    let to = adj.data.len();
+                    index.insert(id(&n), to);
+                    let n = n.borrow();
+                    adj.data.push(n.datum.clone());
+                    adj.edges.push(Vec::new());
+                    stack.extend(n.neighbors().rev().map(|m| (to, m)));
+                    to
+                }
+            };
+            adj.edges[from].push(to);
+        }
+        start
+    }
+
+    // Whether there is a node labelled `target` reachable from `self`,
+    // including `self`. Stops searching as soon as one is found.
+    fn can_reach(&self, target: T) -> bool {
+        if self.datum == target {
+            return true;
+        }
+        let mut seen = HashSet::new();
+        seen.insert(self as NodeId<T>);
+        let mut stack: Vec<_> = self.neighbors().collect();
+        while let Some(n) = stack.pop() {
+            if !seen.insert(id(&n)) {
+                continue;
+            }
+            let n = n.borrow();
+            if n.datum == target {
+                return true;
+            }
+            stack.extend(n.neighbors());
+        }
+        false
+    }
+
+    fn first(&self) -> Option<Rc<RefCell<Node<T>>>> {
+        self.edges.first().cloned()
+    }
+}
+
+impl<T: Clone + Eq + Hash + fmt::Display> Node<T> {
+    // Renders the reachable graph in Graphviz's DOT language.
+    fn to_dot(&self) -> String {
+        let adj = self.adjacency();
+        let mut dot = String::from("digraph {\n");
+        for d in &adj.data {
+            dot.push_str(&format!("    {};\n", dot_id(d)));
+        }
+        for (from, targets) in adj.edges.iter().enumerate() {
+            for &to in targets {
+                dot.push_str(&format!("    {} -> {};\n",
+                                      dot_id(&adj.data[from]),
+                                      dot_id(&adj.data[to])));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Renders the reachable graph as GraphML, for tools such as Gephi and yEd.
+    // Labels are used as node ids, so nodes which share a label become one
+    // node.
+    fn to_graphml(&self) -> String {
+        let adj = self.adjacency();
+        let ids: Vec<_> = adj.data.iter().map(xml_escape).collect();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        xml.push_str("  <graph edgedefault=\"directed\">\n");
+        let mut seen = HashSet::new();
+        for id in &ids {
+            if seen.insert(id) {
+                xml.push_str(&format!("    <node id=\"{}\"/>\n", id));
+            }
+        }
+        for (from, targets) in adj.edges.iter().enumerate() {
+            for &to in targets {
+                xml.push_str(&format!("    <edge source=\"{}\" target=\"{}\"/>\n",
+                                      ids[from],
+                                      ids[to]));
+            }
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    // Checks the graph reachable from `self` for things which are allowed but
+    // usually mistakes: self-loops, parallel edges, and weak edges whose
+    // target has been freed. Strong edges can't dangle, an `Rc` keeps its
+    // target alive. Returns a description of each problem found.
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        self.edge_problems(&mut problems);
+        let mut seen = HashSet::new();
+        seen.insert(self as NodeId<T>);
+        let mut stack: Vec<_> = self.edges.iter().rev().cloned().collect();
+        while let Some(n) = stack.pop() {
+            if !seen.insert(id(&n)) {
+                continue;
+            }
+            let n = n.borrow();
+            n.edge_problems(&mut problems);
+            stack.extend(n.edges.iter().rev().cloned());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    // The problems `validate` looks for in the edges out of `self`.
+    fn edge_problems(&self, problems: &mut Vec<String>) {
+        let mut counts: HashMap<NodeId<T>, usize> = HashMap::new();
+        for n in &self.edges {
+            *counts.entry(id(n)).or_insert(0) += 1;
+        }
+        for n in &self.edges {
+            // Each target is reported once, however many edges lead to it.
+            let Some(count) = counts.remove(&id(n)) else { continue };
+            if std::ptr::eq(id(n), self) {
+                problems.push(format!("{} self-loop(s) on {}", count, self.datum));
+            } else if count > 1 {
+                problems.push(format!("{} edges from {} to {}",
+                                      count,
+                                      self.datum,
+                                      n.borrow().datum));
+            }
+        }
+        let dangling = self.weak_edges.iter().filter(|w| w.upgrade().is_none()).count();
+        if dangling > 0 {
+            problems.push(format!("{} dangling weak edge(s) from {}", dangling, self.datum));
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + Eq + Hash + Serialize> Node<T> {
+    fn to_json(&self) -> serde_json::Result<String> {
+        let adj = self.adjacency();
+        let json = GraphJson {
+            nodes: adj.data.clone(),
+            edges: adj.edges.iter()
+                .enumerate()
+                .flat_map(|(from, targets)| targets.iter().map(move |&to| (from, to)))
+                .collect(),
+        };
+        serde_json::to_string(&json)
+    }
+}
+
+// Only shows the immediate edges, e.g., `A -> [B, C, D]`, so this terminates
+// even on cyclic graphs.
+impl<T: Clone + Eq + Hash + fmt::Display> fmt::Display for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> [", self.datum)?;
+        for (i, n) in self.neighbors().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", n.borrow().datum)?;
+        }
+        write!(f, "]")
+    }
+}
+
+// A derived `Debug` would recurse forever around cycles. Instead, print each
+// reachable node once, mapped to the labels of its edges, e.g.,
+// `{"A": ["B", "C", "D"], "B": [], ...}`.
+impl<T: Clone + Eq + Hash + fmt::Debug> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let adj = self.adjacency();
+        let targets = |ts: &Vec<usize>| ts.iter().map(|&t| &adj.data[t]).collect::<Vec<_>>();
+        f.debug_map()
+            .entries(adj.data.iter().zip(&adj.edges).map(|(d, ts)| (d, targets(ts))))
+            .finish()
+    }
+}
+
+impl<T: Clone + Eq + Hash> Graph for Rc<RefCell<Node<T>>> {
+    type NodeRef = Rc<RefCell<Node<T>>>;
+    type Datum = T;
+
+    fn traverse<F>(&self, f: &F)
+        where F: Fn(&T)
+    {
+        self.borrow().traverse(f, &mut HashSet::new());
+    }
+
+    fn first(&self) -> Option<Rc<RefCell<Node<T>>>> {
+        self.borrow().first()
+    }
+
+    fn node_count(&self) -> usize {
+        self.borrow().node_count()
+    }
+}
+
+// Panics if `from` is currently borrowed.
+fn add_edge<T>(from: &Rc<RefCell<Node<T>>>, to: &Rc<RefCell<Node<T>>>) {
+    from.borrow_mut().edges.push(to.clone());
+}
+
+// Relabels `node` in place. Queries keyed by datum, such as `find` or
+// `dfs_timestamps`, may then match the wrong node, so prefer a seen-set of
+// `NodeId`s when writing traversals over data which can change. Panics if
+// `node` is borrowed.
+fn set_datum<T>(node: &Rc<RefCell<Node<T>>>, new_datum: T) {
+    node.borrow_mut().datum = new_datum;
+}
+
+// Like calling `add_edge` for each of `targets`, but grows `from`'s edges at
+// most once. Panics if `from` is currently borrowed.
+fn add_edges<T>(from: &Rc<RefCell<Node<T>>>, targets: &[Rc<RefCell<Node<T>>>]) {
+    let mut from = from.borrow_mut();
+    from.edges.reserve(targets.len());
+    from.edges.extend(targets.iter().cloned());
+}
+
+// Gives back any spare capacity in the edge lists of every node reachable from
+// `start`, e.g., once a graph has been built and won't grow any more.
+fn compact<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>) {
+    for node in dfs_iter(start) {
+        let mut node = node.borrow_mut();
+        node.edges.shrink_to_fit();
+        node.weak_edges.shrink_to_fit();
+    }
+}
+
+// Removes the first edge from `from` to a node labelled `target`, returning
+// whether there was one. If there are parallel edges to `target`, only the
+// first is removed, call this in a loop to remove them all.
+fn remove_edge<T: PartialEq>(from: &Rc<RefCell<Node<T>>>, target: T) -> bool {
+    // Find the edge before borrowing mutably, the edge might be a self-loop.
+    let pos = from.borrow().edges.iter().position(|n| n.borrow().datum == target);
+    match pos {
+        Some(i) => {
+            from.borrow_mut().edges.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+// Like `remove_edge`, but takes constant time rather than shifting every later
+// edge down. The price is that the last edge is moved into the gap, so the
+// remaining edges are no longer in the order they were added, which changes
+// the order of traversals from `from`.
+fn remove_edge_unordered<T: PartialEq>(from: &Rc<RefCell<Node<T>>>, target: T) -> bool {
+    let pos = from.borrow().edges.iter().position(|n| n.borrow().datum == target);
+    match pos {
+        Some(i) => {
+            from.borrow_mut().edges.swap_remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+// Detaches the node labelled `datum` by removing every edge to it from the
+// graph reachable from `start`. Nothing else then holds the node's `Rc`, so
+// once any outside references are gone it is freed, taking its own edges with
+// it; that includes the `C -> A` back-edge in the example, which is what kept
+// the cycle alive. `start` itself is never freed, only cut off from its
+// predecessors.
+fn remove_node<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>, datum: T) {
+    retain_edges(start, |_, to| *to != datum);
+}
+
+// Removes every edge `(from, to)` in the graph reachable from `start` for which
+// `keep` returns false, weak edges included. Weak edges whose target has
+// already been freed are removed too. Nodes are found before any edges are
+// removed, so nodes cut off part way through are still visited.
+fn retain_edges<T, P>(start: &Rc<RefCell<Node<T>>>, keep: P)
+    where T: Clone + Eq + Hash,
+          P: Fn(&T, &T) -> bool
+{
+    // Collect the nodes first, since we can't change edges while iterating.
+    let nodes: Vec<_> = dfs_iter(start).collect();
+    for node in nodes {
+        // Work out what to keep before borrowing mutably, in case of
+        // self-loops.
+        let kept: Vec<_> = {
+            let from = node.borrow();
+            from.edges.iter().map(|n| keep(&from.datum, &n.borrow().datum)).collect()
+        };
+        let mut kept = kept.into_iter();
+        node.borrow_mut().edges.retain(|_| kept.next().unwrap());
+
+        let kept: Vec<_> = {
+            let from = node.borrow();
+            from.weak_edges.iter()
+                .map(|n| n.upgrade().is_some_and(|n| keep(&from.datum, &n.borrow().datum)))
+                .collect()
+        };
+        let mut kept = kept.into_iter();
+        node.borrow_mut().weak_edges.retain(|_| kept.next().unwrap());
+    }
+}
+
+// Merges the node labelled `b` into the node labelled `a`, both found from
+// `start`: `b`'s edges move to `a`, edges to `b` now point to `a`, and `b` is
+// left with no edges in or out. Edges between `a` and `b` would become
+// self-loops, so they are dropped. Returns false, changing nothing, if either
+// node can't be found or they are the same node.
+fn contract_edge<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>, a: T, b: T) -> bool {
+    let nodes: Vec<_> = dfs_iter(start).collect();
+    let find = |datum: &T| nodes.iter().find(|n| n.borrow().datum == *datum).cloned();
+    let (a, b) = match (find(&a), find(&b)) {
+        (Some(a), Some(b)) if !Rc::ptr_eq(&a, &b) => (a, b),
+        _ => return false,
+    };
+
+    // Only pointers are compared from here on, so there's no need to borrow
+    // the targets of edges, which might include the node being changed.
+    let is_weak = |n: &Weak<RefCell<Node<T>>>, target: &Rc<RefCell<Node<T>>>| {
+        n.as_ptr() == Rc::as_ptr(target)
+    };
+
+    let (edges, weak_edges) = {
+        let mut b = b.borrow_mut();
+        (b.edges.split_off(0), b.weak_edges.split_off(0))
+    };
+    for node in &nodes {
+        if Rc::ptr_eq(node, &b) {
+            continue;
+        }
+        let is_a = Rc::ptr_eq(node, &a);
+        let mut node = node.borrow_mut();
+        if is_a {
+            node.edges.retain(|e| !Rc::ptr_eq(e, &b));
+            node.weak_edges.retain(|e| !is_weak(e, &b));
+            continue;
+        }
+        for e in &mut node.edges {
+            if Rc::ptr_eq(e, &b) {
+                *e = a.clone();
+            }
+        }
+        for e in &mut node.weak_edges {
+            if is_weak(e, &b) {
+                *e = Rc::downgrade(&a);
+            }
+        }
+    }
+
+    let mut merged = a.borrow_mut();
+    merged.edges.extend(edges.into_iter()
+        .filter(|e| !Rc::ptr_eq(e, &a) && !Rc::ptr_eq(e, &b)));
+    merged.weak_edges.extend(weak_edges.into_iter()
+        .filter(|e| !is_weak(e, &a) && !is_weak(e, &b)));
+    true
+}
+
+// The path with the fewest edges from `start` to `target`, found by searching
+// forwards from `start` and backwards from `target` (using `reverse`, which
+// must cover `start`) until the searches meet. Each search only has to get
+// about half way, which for large graphs means visiting far fewer nodes.
+fn bidirectional_shortest_path<T>(start: &Rc<RefCell<Node<T>>>,
+                                  target: &Rc<RefCell<Node<T>>>,
+                                  reverse: &ReverseAdjacency<T>) -> Option<Vec<T>>
+    where T: Clone + Eq + Hash
+{
+    if Rc::ptr_eq(start, target) {
+        return Some(vec![start.borrow().datum.clone()]);
+    }
+    let mut forward = HashMap::new();
+    let mut backward = HashMap::new();
+    forward.insert(id(start), (0, None));
+    backward.insert(id(target), (0, None));
+    let mut forward_frontier = vec![start.clone()];
+    let mut backward_frontier = vec![target.clone()];
+    let mut best = None;
+
+    // We always finish a whole level before checking whether the searches have
+    // met, since the first meeting found isn't necessarily the best one.
+    while best.is_none() && !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+        if forward_frontier.len() <= backward_frontier.len() {
+            forward_frontier = search_level(&forward_frontier,
+                                            &mut forward,
+                                            &backward,
+                                            &mut best,
+                                            |n| n.borrow().edges.clone(),
+                                            false);
+        } else {
+            backward_frontier = search_level(&backward_frontier,
+                                             &mut backward,
+                                             &forward,
+                                             &mut best,
+                                             |n| reverse.predecessors(n).to_vec(),
+                                             true);
+        }
+    }
+
+    let (_, from, to) = best?;
+    let mut path = Vec::new();
+    let mut node = Some(from);
+    while let Some(n) = node {
+        path.push(n.borrow().datum.clone());
+        node = forward[&id(&n)].1.clone();
+    }
+    path.reverse();
+    let mut node = Some(to);
+    while let Some(n) = node {
+        path.push(n.borrow().datum.clone());
+        node = backward[&id(&n)].1.clone();
+    }
+    Some(path)
+}
+
+// Expands one level of one side of `bidirectional_shortest_path`, returning
+// the next level. Whenever an edge reaches a node found by the `other` side,
+// `best` is updated if that makes a shorter path. `backwards` says which side
+// this is, so the meeting edge can be recorded the right way round.
+fn search_level<T, N>(frontier: &[Rc<RefCell<Node<T>>>],
+                      visited: &mut Visited<T>,
+                      other: &Visited<T>,
+                      best: &mut Option<Meeting<T>>,
+                      neighbors: N,
+                      backwards: bool) -> Vec<Rc<RefCell<Node<T>>>>
+    where N: Fn(&Rc<RefCell<Node<T>>>) -> Vec<Rc<RefCell<Node<T>>>>
+{
+    let mut next = Vec::new();
+    for n in frontier {
+        let depth = visited[&id(n)].0;
+        for m in neighbors(n) {
+            if let Some(&(other_depth, _)) = other.get(&id(&m)) {
+                let len = depth + 1 + other_depth;
+                if best.as_ref().is_none_or(|b| len < b.0) {
+                    *best = if backwards {
+                        Some((len, m.clone(), n.clone()))
+                    } else {
+                        Some((len, n.clone(), m.clone()))
+                    };
+                }
+            }
+            if let Entry::Vacant(e) = visited.entry(id(&m)) {
+                e.insert((depth + 1, Some(n.clone())));
+                next.push(m);
+            }
+        }
+    }
+    next
+}
+
+// Like `Node::adjacency`, but covering everything reachable from any of
+// `roots`.
+fn adjacency_from<T: Clone + Eq + Hash>(roots: &[Rc<RefCell<Node<T>>>]) -> Adjacency<T> {
+    let mut index = HashMap::new();
+    let mut adj = Adjacency::empty();
+    for r in roots {
+        r.borrow().collect(&mut index, &mut adj);
+    }
+    adj
+}
+
+// The pieces the graph reachable from `roots` falls into if we ignore the
+// direction of edges, like `connected_components` but returning the data in
+// each piece. This uses union-find rather than searching: each node starts in
+// a set of its own and each edge merges the sets of its ends. Components, and
+// the data in them, are in the order `traverse` would find them.
+fn weakly_connected_components<T>(roots: &[Rc<RefCell<Node<T>>>]) -> Vec<Vec<T>>
+    where T: Clone + Eq + Hash
+{
+    // Follows `parent` up to the representative of `i`'s set, pointing nodes
+    // at their grandparents on the way so that later searches are shorter.
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let adj = adjacency_from(roots);
+    let mut parent: Vec<_> = (0..adj.data.len()).collect();
+    for (from, targets) in adj.edges.iter().enumerate() {
+        for &to in targets {
+            let (a, b) = (find(&mut parent, from), find(&mut parent, to));
+            parent[a] = b;
+        }
+    }
+
+    let mut index = HashMap::new();
+    let mut components: Vec<Vec<T>> = Vec::new();
+    for (i, datum) in adj.data.iter().enumerate() {
+        let root = find(&mut parent, i);
+        let next = components.len();
+        let c = *index.entry(root).or_insert(next);
+        if c == next {
+            components.push(Vec::new());
+        }
+        components[c].push(datum.clone());
+    }
+    components
+}
+
+// The data of everything reachable from the node labelled `datum`, which is
+// itself reachable from `start`, not counting that node. Empty if there is no
+// such node.
+fn descendants<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>, datum: T) -> HashSet<T> {
+    match find(start, datum) {
+        Some(n) => n.borrow().adjacency().data.into_iter().skip(1).collect(),
+        None => HashSet::new(),
+    }
+}
+
+// The data of every node, reachable from `start`, which can reach the node
+// labelled `datum`, not counting that node itself. These are the descendants
+// of the node in the transposed graph.
+fn ancestors<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>, datum: T) -> HashSet<T> {
+    let transposed = start.borrow().transpose();
+    let ancestors = transposed.iter()
+        .find(|n| n.borrow().datum == datum)
+        .map(|n| n.borrow().adjacency().data.into_iter().skip(1).collect())
+        .unwrap_or_default();
+    // Break any cycles so the transposed graph is freed.
+    for n in &transposed {
+        n.borrow_mut().edges.clear();
+    }
+    ancestors
+}
+
+// Like `Node::classify`, but for everything reachable from any of `roots`.
+fn classify_from<T: Clone + Eq + Hash>(roots: &[Rc<RefCell<Node<T>>>]) -> GraphClass {
+    adjacency_from(roots).classify()
+}
+
+// The greatest eccentricity of any node reachable from `roots`. Pairs of nodes
+// with no path between them are ignored rather than making this infinite.
+fn diameter<T: Clone + Eq + Hash>(roots: &[Rc<RefCell<Node<T>>>]) -> usize {
+    let adj = adjacency_from(roots);
+    (0..adj.data.len()).map(|i| adj.eccentricity(i)).max().unwrap_or(0)
+}
+
+// Counts the pieces the graph reachable from `roots` falls into if we ignore
+// the direction of edges.
+fn connected_components<T: Clone + Eq + Hash>(roots: &[Rc<RefCell<Node<T>>>]) -> usize {
+    let adj = adjacency_from(roots);
+    let neighbors = adj.undirected();
+    let mut seen = vec![false; adj.data.len()];
+    let mut count = 0;
+    for start in 0..adj.data.len() {
+        if seen[start] {
+            continue;
+        }
+        count += 1;
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            for &n in &neighbors[i] {
+                if !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+    }
+    count
+}
+
+// Reference counting can't free a cycle, so the sample graph (with its
+// `C -> A` edge) is never freed. This moves every edge which closes a cycle
+// (found by a depth-first search from `start`) out of `edges` and into
+// `weak_edges`. What remains is acyclic, so the graph is freed once the last
+// outside reference goes away.
+fn break_cycles<T>(start: &Rc<RefCell<Node<T>>>) {
+    let mut back_edges = Vec::new();
+    find_back_edges(start, &mut HashSet::new(), &mut HashSet::new(), &mut back_edges);
+    // Going backwards means we remove each node's later edges first, so the
+    // earlier indices remain valid.
+    for (node, i) in back_edges.into_iter().rev() {
+        let mut node = node.borrow_mut();
+        let target = node.edges.remove(i);
+        node.weak_edges.push(Rc::downgrade(&target));
+    }
+}
+
+// Records each edge from a node on the current path back to a node on the path
+// as a `(from, index into edges)` pair.
+fn find_back_edges<T>(node: &Rc<RefCell<Node<T>>>,
+                      seen: &mut HashSet<NodeId<T>>,
+                      path: &mut HashSet<NodeId<T>>,
+                      back_edges: &mut Vec<(Rc<RefCell<Node<T>>>, usize)>) {
+    seen.insert(id(node));
+    path.insert(id(node));
+    let edges = node.borrow().edges.clone();
+    for (i, n) in edges.iter().enumerate() {
+        if path.contains(&id(n)) {
+            back_edges.push((node.clone(), i));
+        } else if !seen.contains(&id(n)) {
+            find_back_edges(n, seen, path, back_edges);
+        }
+    }
+    path.remove(&id(node));
+}
+
+// Breadth-first search from `start` for the node labelled `datum`. Like
+// `dfs_iter`, this takes the `Rc` so that `start` itself can be returned.
+fn find<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>, datum: T)
+                              -> Option<Rc<RefCell<Node<T>>>> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(start.clone());
+    while let Some(n) = queue.pop_front() {
+        if !seen.insert(id(&n)) {
+            continue;
+        }
+        if n.borrow().datum == datum {
+            return Some(n);
+        }
+        queue.extend(n.borrow().neighbors());
+    }
+    None
+}
+
+// The first neighbour of the node labelled `datum`, or `None` if there is no
+// such node reachable from `start` or it has no edges.
+fn first_neighbor_of<T: Clone + Eq + Hash>(start: &Rc<RefCell<Node<T>>>, datum: T)
+                                           -> Option<Rc<RefCell<Node<T>>>> {
+    find(start, datum).and_then(|n| n.borrow().first())
+}
+
+// Iterates over the graph in the same order as `traverse`. This takes the
+// `Rc` rather than a `&Node` so that the start node can be yielded too.
+fn dfs_iter<T>(start: &Rc<RefCell<Node<T>>>) -> DfsIter<T> {
+    DfsIter {
+        stack: vec![start.clone()],
+        seen: HashSet::new(),
+    }
+}
+
+// Builds a graph from `(from, to)` pairs, with one node per distinct datum.
+// Returns the node for the first datum in `edges`.
+//
+// Panics if `edges` is empty.
+fn from_adjacency<T: Clone + Eq + Hash>(edges: &[(T, T)]) -> Rc<RefCell<Node<T>>> {
+    edges.iter()
+        .fold(GraphBuilder::new(), |b, (from, to)| b.edge(from.clone(), to.clone()))
+        .build()
+}
+
+// An Erdős–Rényi random graph: `n` nodes labelled `n0` to `n{n-1}`, with each
+// possible edge between distinct nodes present with probability `edge_prob`.
+// The same seed always gives the same graph. Some nodes may not be reachable
+// from `n0`, so this returns all of them, in label order.
+fn random_graph(n: usize, edge_prob: f64, seed: u64) -> Vec<Rc<RefCell<Node<String>>>> {
+    let mut rng = Rng(seed);
+    let nodes: Vec<_> = (0..n).map(|i| Node::new(format!("n{}", i))).collect();
+    for from in &nodes {
+        for to in &nodes {
+            if !Rc::ptr_eq(from, to) && rng.next_f64() < edge_prob {
+                add_edge(from, to);
+            }
+        }
+    }
+    nodes
+}
+
+// Combines the graphs reachable from `a` and `b` into a new graph, where nodes
+// with the same datum become a single node with the edges of both. Parallel
+// edges, in either input or between them, are only copied once. Returns the
+// node for `a`'s datum, so parts of `b` which that node can't reach are lost.
+fn merge<T: Clone + Eq + Hash>(a: &Rc<RefCell<Node<T>>>,
+                               b: &Rc<RefCell<Node<T>>>) -> Rc<RefCell<Node<T>>> {
+    let adj = adjacency_from(&[a.clone(), b.clone()]);
+    let mut builder = GraphBuilder::new().node(adj.data[0].clone());
+    let mut seen = HashSet::new();
+    for (from, targets) in adj.edges.iter().enumerate() {
+        builder = builder.node(adj.data[from].clone());
+        for &to in targets {
+            let edge = (adj.data[from].clone(), adj.data[to].clone());
+            if seen.insert(edge.clone()) {
+                builder = builder.edge(edge.0, edge.1);
+            }
+        }
+    }
+    builder.build()
+}
+
+// Compares the graphs reachable from `old` and `new` by label, so nodes match
+// if they have the same datum, wherever they are. Edges are compared as pairs
+// of labels, so parallel edges only count once.
+fn diff<T: Clone + Eq + Hash>(old: &Rc<RefCell<Node<T>>>,
+                              new: &Rc<RefCell<Node<T>>>) -> GraphDiff<T> {
+    // Each label once and each edge once, in order.
+    fn labels<T: Clone + Eq + Hash>(node: &Rc<RefCell<Node<T>>>) -> (Vec<T>, Vec<(T, T)>) {
+        let adj = node.borrow().adjacency();
+        let mut seen = HashSet::new();
+        let mut edges = Vec::new();
+        for (from, targets) in adj.edges.iter().enumerate() {
+            for &to in targets {
+                let edge = (adj.data[from].clone(), adj.data[to].clone());
+                if seen.insert(edge.clone()) {
+                    edges.push(edge);
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        let data = adj.data.into_iter().filter(|d| seen.insert(d.clone())).collect();
+        (data, edges)
+    }
+
+    let (old_nodes, old_edges) = labels(old);
+    let (new_nodes, new_edges) = labels(new);
+    let old_node_set: HashSet<_> = old_nodes.iter().cloned().collect();
+    let new_node_set: HashSet<_> = new_nodes.iter().cloned().collect();
+    let old_edge_set: HashSet<_> = old_edges.iter().cloned().collect();
+    let new_edge_set: HashSet<_> = new_edges.iter().cloned().collect();
+    GraphDiff {
+        added_nodes: new_nodes.into_iter().filter(|d| !old_node_set.contains(d)).collect(),
+        removed_nodes: old_nodes.into_iter().filter(|d| !new_node_set.contains(d)).collect(),
+        added_edges: new_edges.into_iter().filter(|e| !old_edge_set.contains(e)).collect(),
+        removed_edges: old_edges.into_iter().filter(|e| !new_edge_set.contains(e)).collect(),
+    }
+}
+
+// Reads one `from,to` edge per line, ignoring blank lines and lines starting
+// with `#`. Returns the node for the first label on the first edge.
+fn from_edge_list(input: &str) -> Result<Rc<RefCell<Node<String>>>, ParseError> {
+    let mut builder = GraphBuilder::new();
+    let mut empty = true;
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let labels: Vec<_> = line.split(',').map(str::trim).collect();
+        match labels[..] {
+            [from, to] if !from.is_empty() && !to.is_empty() => {
+                builder = builder.edge(from.to_owned(), to.to_owned());
+                empty = false;
+            }
+            _ => return Err(ParseError::Malformed(i + 1, line.to_owned())),
+        }
+    }
+    if empty {
+        return Err(ParseError::Empty);
+    }
+    Ok(builder.build())
+}
+
+// Labels can't be borrowed from `json`, so to read string labels use
+// `Node<String>` rather than `Node<&'static str>`.
+#[cfg(feature = "serde")]
+fn from_json<T>(json: &str) -> serde_json::Result<Rc<RefCell<Node<T>>>>
+    where T: Clone + Eq + Hash + DeserializeOwned
+{
+    use serde::de::Error;
+
+    let json: GraphJson<T> = serde_json::from_str(json)?;
+    let nodes: Vec<_> = json.nodes.into_iter().map(Node::new).collect();
+    for (from, to) in json.edges {
+        if from >= nodes.len() || to >= nodes.len() {
+            return Err(serde_json::Error::custom("edge refers to a missing node"));
+        }
+        nodes[from].borrow_mut().edges.push(nodes[to].clone());
+    }
+    nodes.into_iter().next().ok_or_else(|| serde_json::Error::custom("graph has no nodes"))
+}
+
+fn dot_id<T: fmt::Display>(datum: &T) -> String {
+    format!("\"{}\"", datum.to_string().replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Escapes a datum for use in an XML attribute.
+fn xml_escape<T: fmt::Display>(datum: &T) -> String {
+    datum.to_string()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
 }
 
 fn foo(node: &Node) {
     println!("foo: {}", node.datum);
 }
 
-fn init() -> Rc<RefCell<Node>> {
+pub(crate) fn init() -> Rc<RefCell<Node>> {
     let root = Node::new("A");
 
     let b = Node::new("B");
@@ -66,6 +2568,1305 @@ pub fn main() {
     let g = init();
     let g = g.borrow();
     g.traverse(&|d| println!("{}", d), &mut HashSet::new());
-    let f = g.first();
-    foo(&*f.borrow());
+    if let Some(f) = g.first() {
+        foo(&f.borrow());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph;
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::hash::Hasher;
+    use std::rc::Rc;
+    use std::slice;
+    use std::sync::Mutex;
+    use std::thread;
+
+    #[test]
+    fn first_is_none_without_edges() {
+        let g = init();
+        assert_eq!(g.borrow().first().unwrap().borrow().datum, "B");
+        assert!(Node::new("X").borrow().first().is_none());
+    }
+
+    // Collects the data passed to the callback of a traversal, in visiting order.
+    fn order<G>(g: G) -> Vec<&'static str>
+        where G: Fn(&dyn Fn(&&'static str))
+    {
+        let v = RefCell::new(Vec::new());
+        g(&|d| v.borrow_mut().push(*d));
+        v.into_inner()
+    }
+
+    #[test]
+    fn traverse_bfs_visits_in_breadth_first_order() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(order(|f| g.traverse_bfs(&f)), vec!["A", "B", "C", "D", "E", "F"]);
+    }
+
+    #[test]
+    fn traverse_iter_matches_traverse() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(order(|f| g.traverse_iter(&f)), order(|f| g.traverse(&f, &mut HashSet::new())));
+    }
+
+    #[test]
+    fn traverse_iter_handles_long_chains() {
+        let root = Node::new("n0");
+        let mut prev = root.clone();
+        for i in 1..100_000 {
+            let label: &'static str = Box::leak(format!("n{}", i).into_boxed_str());
+            let n = Node::new(label);
+            prev.borrow_mut().edges.push(n.clone());
+            prev = n;
+        }
+        let count = Cell::new(0);
+        root.borrow().traverse_iter(&|_| count.set(count.get() + 1));
+        assert_eq!(count.get(), 100_000);
+
+        // Unlink the chain a node at a time so dropping it doesn't recurse.
+        let mut next = root.borrow_mut().edges.pop();
+        while let Some(n) = next {
+            next = n.borrow_mut().edges.pop();
+        }
+    }
+
+    #[test]
+    fn adjacency_handles_long_chains() {
+        let root = Node::new("n0");
+        let mut prev = root.clone();
+        for i in 1..100_000 {
+            let label: &'static str = Box::leak(format!("n{}", i).into_boxed_str());
+            let n = Node::new(label);
+            prev.borrow_mut().edges.push(n.clone());
+            prev = n;
+        }
+        let last = prev.borrow().datum;
+        assert_eq!(root.borrow().shortest_path(last).map(|p| p.len()), Some(100_000));
+        assert_eq!(root.borrow().topological_sort().map(|o| o.len()).ok(), Some(100_000));
+
+        let mut next = root.borrow_mut().edges.pop();
+        while let Some(n) = next {
+            next = n.borrow_mut().edges.pop();
+        }
+    }
+
+    #[test]
+    fn counts_nodes_and_edges() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.node_count(), 6);
+        assert_eq!(g.edge_count(), 6);
+    }
+
+    // A -> B, C; C -> D. No cycles.
+    fn tree() -> Rc<RefCell<Node>> {
+        let a = Node::new("A");
+        let b = Node::new("B");
+        let c = Node::new("C");
+        let d = Node::new("D");
+        a.borrow_mut().edges.push(b);
+        a.borrow_mut().edges.push(c.clone());
+        c.borrow_mut().edges.push(d);
+        a
+    }
+
+    #[test]
+    fn has_cycle_finds_back_edges() {
+        assert!(init().borrow().has_cycle());
+        assert!(!tree().borrow().has_cycle());
+    }
+
+    #[test]
+    fn topological_sort_orders_dags_and_rejects_cycles() {
+        let t = tree();
+        let sorted = t.borrow().topological_sort().unwrap();
+        let pos = |d| sorted.iter().position(|&x| x == d).unwrap();
+        assert_eq!(sorted.len(), 4);
+        assert!(pos("A") < pos("B") && pos("A") < pos("C") && pos("C") < pos("D"));
+        assert!(init().borrow().topological_sort().is_err());
+    }
+
+    #[test]
+    fn to_dot_lists_every_edge() {
+        let s = init().borrow().to_dot();
+        assert!(s.starts_with("digraph {\n"));
+        assert!(s.contains("    \"C\" -> \"A\";\n"));
+        assert_eq!(s.lines().filter(|l| l.contains("->")).count(), 6);
+        assert_eq!(dot_id(&"a\"b\\"), "\"a\\\"b\\\\\"");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trips() {
+        let g: Rc<RefCell<Node<String>>> = from_json(&init().borrow().to_json().unwrap()).unwrap();
+        assert_eq!(g.borrow().to_dot(), init().borrow().to_dot());
+        assert!(from_json::<String>("{\"nodes\":[\"a\"],\"edges\":[[0,3]]}").is_err());
+        assert!(from_json::<String>("{\"nodes\":[],\"edges\":[]}").is_err());
+    }
+
+    #[test]
+    fn from_adjacency_builds_the_sample_graph() {
+        let edges = [("A", "B"), ("A", "C"), ("A", "D"), ("C", "E"), ("C", "F"), ("C", "A")];
+        assert_eq!(from_adjacency(&edges).borrow().to_dot(), init().borrow().to_dot());
+    }
+
+    #[test]
+    fn shortest_path_follows_fewest_edges() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.shortest_path("F"), Some(vec!["A", "C", "F"]));
+        assert_eq!(g.shortest_path("A"), Some(vec!["A"]));
+        assert_eq!(g.shortest_path("Z"), None);
+        let t = tree();
+        assert_eq!(t.borrow().edges[0].borrow().shortest_path("A"), None);
+    }
+
+    #[test]
+    fn dfs_iter_matches_traverse() {
+        let g = init();
+        let v: Vec<_> = dfs_iter(&g).map(|n| n.borrow().datum).collect();
+        assert_eq!(v, order(|f| g.borrow().traverse(&f, &mut HashSet::new())));
+    }
+
+    #[test]
+    fn add_edge_links_nodes() {
+        let a = Node::new("a");
+        let b = Node::new("b");
+        assert_eq!(a.borrow().node_count(), 1);
+        add_edge(&a, &b);
+        assert_eq!(order(|f| a.borrow().traverse(&f, &mut HashSet::new())), vec!["a", "b"]);
+        add_edge(&a, &a);
+        assert_eq!(a.borrow().edge_count(), 2);
+        a.borrow_mut().edges.clear();
+    }
+
+    #[test]
+    fn remove_edge_removes_one_edge_at_a_time() {
+        let a = Node::new("a");
+        let b = Node::new("b");
+        add_edge(&a, &b);
+        add_edge(&a, &a);
+        add_edge(&a, &b);
+        assert!(!remove_edge(&a, "z"));
+        assert!(remove_edge(&a, "b"));
+        assert_eq!(a.borrow().edges.len(), 2);
+        assert!(remove_edge(&a, "a"));
+        assert!(remove_edge(&a, "b"));
+        assert!(!remove_edge(&a, "b"));
+    }
+
+    #[test]
+    fn neighbors_yields_direct_successors() {
+        let g = init();
+        let v: Vec<_> = g.borrow().neighbors().map(|n| n.borrow().datum).collect();
+        assert_eq!(v, ["B", "C", "D"]);
+    }
+
+    #[test]
+    fn degrees_count_incoming_and_outgoing_edges() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.out_degree(), 3);
+        assert_eq!(g.in_degree("A"), 1);
+        assert_eq!(g.degree("A"), 4);
+        assert_eq!(g.degree("C"), 4);
+        assert_eq!(g.degree("E"), 1);
+        assert_eq!(g.degree("Q"), 0);
+    }
+
+    #[test]
+    fn find_searches_reachable_nodes() {
+        let g = init();
+        assert_eq!(find(&g, "E").unwrap().borrow().datum, "E");
+        assert!(Rc::ptr_eq(&find(&g, "A").unwrap(), &g));
+        assert!(find(&g, "Q").is_none());
+
+        // The start node is found even when it isn't on a cycle.
+        let t = GraphBuilder::new().edge("A", "B").build();
+        assert!(Rc::ptr_eq(&find(&t, "A").unwrap(), &t));
+    }
+
+    #[test]
+    fn display_shows_direct_edges() {
+        let g = init();
+        assert_eq!(g.borrow().to_string(), "A -> [B, C, D]");
+        assert_eq!(g.borrow().edges[0].borrow().to_string(), "B -> []");
+    }
+
+    #[test]
+    fn debug_terminates_on_cycles() {
+        let g = init();
+        let expected = r#"{"A": ["B", "C", "D"], "B": [], "C": ["E", "F", "A"], "#.to_string()
+            + r#""E": [], "F": [], "D": []}"#;
+        assert_eq!(format!("{:?}", g.borrow()), expected);
+    }
+
+    #[test]
+    fn works_with_other_datum_types() {
+        let g = from_adjacency(&[(1u32, 2), (2, 3), (3, 1)]);
+        assert_eq!(g.borrow().node_count(), 3);
+        assert!(g.borrow().has_cycle());
+        assert_eq!(g.borrow().shortest_path(3), Some(vec![1, 2, 3]));
+        let s = from_adjacency(&[("x".to_string(), "y".to_string())]);
+        assert_eq!(s.borrow().to_string(), "x -> [y]");
+    }
+
+    #[test]
+    fn deep_clone_copies_every_node() {
+        let g = init();
+        let c = g.borrow().deep_clone();
+        assert_eq!(format!("{:?}", g.borrow()), format!("{:?}", c.borrow()));
+        assert!(!Rc::ptr_eq(&g, &c));
+        let gc = find(&g, "C").unwrap();
+        let cc = find(&c, "C").unwrap();
+        assert!(!Rc::ptr_eq(&gc, &cc));
+        assert!(Rc::ptr_eq(&cc.borrow().edges[2], &c));
+    }
+
+    #[test]
+    fn traverse_postorder_visits_children_first() {
+        let t = tree();
+        assert_eq!(order(|f| t.borrow().traverse_postorder(&f)), vec!["B", "D", "C", "A"]);
+        let g = init();
+        assert_eq!(order(|f| g.borrow().traverse_postorder(&f)),
+                   vec!["B", "E", "F", "C", "D", "A"]);
+    }
+
+    #[test]
+    fn traverse_depth_limited_stops_at_max_depth() {
+        let g = init();
+        let v = RefCell::new(Vec::new());
+        g.borrow().traverse_depth_limited(&|d, k| v.borrow_mut().push((*d, k)), 1);
+        assert_eq!(v.into_inner(), vec![("A", 0), ("B", 1), ("C", 1), ("D", 1)]);
+
+        // D is out of reach along A -> B -> C -> D, but not via A -> C -> D.
+        let h = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "D"), ("A", "C")]);
+        let v = RefCell::new(Vec::new());
+        h.borrow().traverse_depth_limited(&|d, k| v.borrow_mut().push((*d, k)), 2);
+        assert_eq!(v.into_inner(), vec![("A", 0), ("B", 1), ("C", 2), ("D", 2)]);
+    }
+
+    #[test]
+    fn connected_components_ignores_edge_direction() {
+        let a = from_adjacency(&[("a", "b"), ("c", "b")]);
+        let b = find(&a, "b").unwrap();
+        let x = from_adjacency(&[("x", "y")]);
+        assert_eq!(connected_components(&[a.clone(), x]), 2);
+        assert_eq!(connected_components(&[a, b]), 1);
+        assert_eq!(connected_components(&[init()]), 1);
+    }
+
+    #[test]
+    fn strongly_connected_components_groups_cycles() {
+        let mut sccs = init().borrow().strongly_connected_components();
+        for c in &mut sccs {
+            c.sort();
+        }
+        assert_eq!(sccs.len(), 5);
+        assert!(sccs.contains(&vec!["A", "C"]));
+        for x in &["B", "D", "E", "F"] {
+            assert!(sccs.contains(&vec![*x]));
+        }
+    }
+
+    #[test]
+    fn transpose_reverses_every_edge() {
+        let t = init().borrow().transpose();
+        let s: Vec<_> = t.iter().map(|n| n.borrow().to_string()).collect();
+        assert_eq!(s, vec!["A -> [C]", "B -> [A]", "C -> [A]",
+                           "E -> [C]", "F -> [C]", "D -> [A]"]);
+    }
+
+    #[test]
+    fn nodes_with_equal_data_are_distinct() {
+        let r = Node::new("R");
+        add_edge(&r, &Node::new("X"));
+        add_edge(&r, &Node::new("X"));
+        let count = Cell::new(0);
+        r.borrow().traverse(&|d| if *d == "X" { count.set(count.get() + 1) }, &mut HashSet::new());
+        assert_eq!(count.get(), 2);
+        assert_eq!(r.borrow().node_count(), 3);
+        assert_eq!(dfs_iter(&r).count(), 3);
+        assert_eq!(order(|f| r.borrow().traverse_bfs(&f)), vec!["R", "X", "X"]);
+        assert_eq!(order(|f| r.borrow().traverse_iter(&f)), vec!["R", "X", "X"]);
+    }
+
+    #[test]
+    fn implements_graph() {
+        assert_eq!(graph::tests::check(&init()), 6);
+    }
+
+    // Counts how many times it, or any clone of it, is dropped. Identified by its
+    // number alone.
+    #[derive(Clone)]
+    struct Counted(u32, Rc<Cell<usize>>);
+
+    impl PartialEq for Counted {
+        fn eq(&self, other: &Counted) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Counted {}
+
+    impl Hash for Counted {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    #[test]
+    fn break_cycles_lets_the_graph_be_freed() {
+        let drops = Rc::new(Cell::new(0));
+        let before;
+        {
+            let a = Node::new(Counted(1, drops.clone()));
+            let b = Node::new(Counted(2, drops.clone()));
+            let c = Node::new(Counted(3, drops.clone()));
+            add_edge(&a, &b);
+            add_edge(&b, &c);
+            add_edge(&c, &a);
+            add_edge(&c, &c);
+            add_edge(&a, &c);
+            drop((b, c));
+            break_cycles(&a);
+            assert!(!a.borrow().has_cycle());
+            assert_eq!(a.borrow().node_count(), 3);
+            before = drops.get();
+        }
+        assert_eq!(drops.get() - before, 3);
+    }
+
+    #[test]
+    fn break_cycles_keeps_the_downgraded_edges() {
+        let g = init();
+        let (edges, dot) = (g.borrow().edge_count(), g.borrow().to_dot());
+        break_cycles(&g);
+        assert_eq!(g.borrow().edges[1].borrow().edges.len(), 2);
+        assert_eq!(g.borrow().edge_count(), edges);
+        assert_eq!(g.borrow().to_dot(), dot);
+    }
+
+    #[test]
+    fn all_paths_enumerates_simple_paths() {
+        let g = from_adjacency(&[("A", "B"), ("A", "C"), ("B", "D"), ("C", "D"), ("D", "A")]);
+        let mut paths = g.borrow().all_paths("D");
+        paths.sort();
+        assert_eq!(paths, vec![vec!["A", "B", "D"], vec!["A", "C", "D"]]);
+        assert_eq!(g.borrow().all_paths("A"), vec![vec!["A"]]);
+        assert!(g.borrow().all_paths("Z").is_empty());
+    }
+
+    #[test]
+    fn graph_builder_builds_the_sample_graph() {
+        let g = GraphBuilder::new()
+            .node("A")
+            .edge("A", "B").edge("A", "C").edge("A", "D")
+            .edge("C", "E").edge("C", "F").edge("C", "A")
+            .node("Z")
+            .build();
+        assert_eq!(format!("{:?}", g.borrow()), format!("{:?}", init().borrow()));
+        assert!(std::panic::catch_unwind(|| GraphBuilder::<u8>::new().build()).is_err());
+    }
+
+    #[test]
+    fn can_reach_follows_edges() {
+        let g = init();
+        assert!(g.borrow().can_reach("F"));
+        assert!(g.borrow().can_reach("A"));
+        assert!(!g.borrow().can_reach("Z"));
+        let t = tree();
+        assert!(!t.borrow().edges[0].borrow().can_reach("A"));
+    }
+
+    #[test]
+    fn first_neighbor_of_finds_the_node_first() {
+        let g = init();
+        assert_eq!(first_neighbor_of(&g, "A").unwrap().borrow().datum, "B");
+        assert_eq!(first_neighbor_of(&g, "C").unwrap().borrow().datum, "E");
+        assert!(first_neighbor_of(&g, "E").is_none());
+        assert!(first_neighbor_of(&g, "Z").is_none());
+    }
+
+    #[test]
+    fn eccentricity_and_diameter() {
+        let g = init();
+        assert_eq!(g.borrow().eccentricity(), 2);
+        assert_eq!(find(&g, "C").unwrap().borrow().eccentricity(), 2);
+        assert_eq!(find(&g, "E").unwrap().borrow().eccentricity(), 0);
+        assert_eq!(diameter(&[g]), 2);
+        let p = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "D")]);
+        assert_eq!(diameter(&[p]), 3);
+        assert_eq!(diameter::<&str>(&[]), 0);
+    }
+
+    #[test]
+    fn remove_node_drops_it_and_its_edges() {
+        let g = init();
+        let c = Rc::downgrade(&find(&g, "C").unwrap());
+        remove_node(&g, "C");
+        assert_eq!(order(|f| g.borrow().traverse(&f, &mut HashSet::new())), vec!["A", "B", "D"]);
+        assert!(!g.borrow().can_reach("E"));
+        assert!(c.upgrade().is_none());
+        let w = Rc::downgrade(&g);
+        drop(g);
+        assert!(w.upgrade().is_none());
+
+        let s = from_adjacency(&[("A", "A"), ("A", "B"), ("B", "A")]);
+        remove_node(&s, "A");
+        assert_eq!(s.borrow().edges.len(), 1);
+        assert!(s.borrow().first().unwrap().borrow().edges.is_empty());
+
+        let s = from_adjacency(&[("A", "B"), ("B", "A")]);
+        break_cycles(&s);
+        remove_node(&s, "A");
+        assert!(s.borrow().first().unwrap().borrow().weak_edges.is_empty());
+    }
+
+    #[test]
+    fn is_bipartite_rejects_odd_cycles() {
+        assert!(init().borrow().is_bipartite());
+        let even = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "D"), ("D", "A")]);
+        assert!(even.borrow().is_bipartite());
+        let odd = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A")]);
+        assert!(!odd.borrow().is_bipartite());
+        assert!(!from_adjacency(&[("A", "A")]).borrow().is_bipartite());
+        assert!(Node::new("X").borrow().is_bipartite());
+    }
+
+    #[test]
+    fn traverse_levels_reports_bfs_depth() {
+        let g = init();
+        let v = RefCell::new(Vec::new());
+        g.borrow().traverse_levels(&|d, l| v.borrow_mut().push((*d, l)));
+        assert_eq!(v.into_inner(),
+                   vec![("A", 0), ("B", 1), ("C", 1), ("D", 1), ("E", 2), ("F", 2)]);
+        let s = from_adjacency(&[("A", "B"), ("B", "C"), ("A", "C")]);
+        let v = RefCell::new(Vec::new());
+        s.borrow().traverse_levels(&|d, l| v.borrow_mut().push((*d, l)));
+        assert_eq!(v.into_inner(), vec![("A", 0), ("B", 1), ("C", 1)]);
+    }
+
+    #[test]
+    fn to_adjacency_matrix_marks_each_edge() {
+        let (labels, m) = init().borrow().to_adjacency_matrix();
+        assert_eq!(labels, vec!["A", "B", "C", "E", "F", "D"]);
+        let mut edges = Vec::new();
+        for (i, row) in m.iter().enumerate() {
+            for (j, &edge) in row.iter().enumerate() {
+                if edge {
+                    edges.push((labels[i], labels[j]));
+                }
+            }
+        }
+        edges.sort();
+        assert_eq!(edges,
+                   vec![("A", "B"), ("A", "C"), ("A", "D"), ("C", "A"), ("C", "E"), ("C", "F")]);
+    }
+
+    #[test]
+    fn map_relabels_a_copy() {
+        let g = from_adjacency(&[("a", "b"), ("a", "c"), ("c", "a")]);
+        let upper = g.borrow().map(|d| d.to_uppercase());
+        assert_eq!(upper.borrow().to_adjacency_matrix().0, vec!["A", "B", "C"]);
+        assert_eq!(upper.borrow().to_adjacency_matrix().1, g.borrow().to_adjacency_matrix().1);
+        assert_eq!(g.borrow().datum, "a");
+        let ones = g.borrow().map(|_| 1);
+        assert_eq!(ones.borrow().node_count(), 3);
+        assert!(ones.borrow().has_cycle());
+    }
+
+    #[test]
+    fn traverse_until_stops_on_break() {
+        let g = init();
+        let mut v = Vec::new();
+        let r = g.borrow().traverse_until(&mut |d| {
+            v.push(*d);
+            if *d == "C" { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        });
+        assert!(r.is_break());
+        assert_eq!(v, vec!["A", "B", "C"]);
+        let mut n = 0;
+        let r = g.borrow().traverse_until(&mut |_| {
+            n += 1;
+            ControlFlow::Continue(())
+        });
+        assert!(r.is_continue());
+        assert_eq!(n, 6);
+    }
+
+    #[test]
+    fn structurally_eq_compares_shape_and_data() {
+        let g = init();
+        let c = g.borrow().deep_clone();
+        assert!(g.borrow().structurally_eq(&c.borrow()));
+        assert!(g.borrow().structurally_eq(&g.borrow()));
+        remove_edge(&find(&c, "C").unwrap(), "A");
+        assert!(!g.borrow().structurally_eq(&c.borrow()));
+
+        // The same labels and edges, but `y` has two nodes labelled D.
+        let x = from_adjacency(&[("A", "B"), ("A", "C"), ("B", "D"), ("C", "D")]);
+        let y = GraphBuilder::new().edge("A", "B").edge("A", "C").edge("B", "D").build();
+        add_edge(&find(&y, "C").unwrap(), &Node::new("D"));
+        assert!(!x.borrow().structurally_eq(&y.borrow()));
+        assert!(x.borrow().structurally_eq(&x.borrow().deep_clone().borrow()));
+
+        // Edge order doesn't matter.
+        let ab = GraphBuilder::new().edge("A", "B").edge("A", "C").build();
+        let ba = GraphBuilder::new().edge("A", "C").edge("A", "B").build();
+        assert!(ab.borrow().structurally_eq(&ba.borrow()));
+    }
+
+    #[test]
+    fn from_edge_list_parses_and_reports_lines() {
+        let g = from_edge_list("# sample\nA,B\n\n A , C\nC,A\n").unwrap();
+        assert_eq!(g.borrow().to_adjacency_matrix().0, vec!["A", "B", "C"]);
+        assert!(g.borrow().has_cycle());
+        let e = from_edge_list("A,B\nB\n").unwrap_err();
+        assert_eq!(e, ParseError::Malformed(2, "B".to_string()));
+        assert_eq!(e.to_string(), "line 2: expected `from,to`, found \"B\"");
+        assert_eq!(from_edge_list("A,B,C").unwrap_err(), ParseError::Malformed(1, "A,B,C".into()));
+        assert_eq!(from_edge_list("A,").unwrap_err(), ParseError::Malformed(1, "A,".into()));
+        assert_eq!(from_edge_list("#x\n\n").unwrap_err(), ParseError::Empty);
+    }
+
+    #[test]
+    fn reachable_count_handles_large_graphs() {
+        assert_eq!(init().borrow().reachable_count(), 6);
+        let same = init().borrow().map(|_| "A");
+        assert_eq!(same.borrow().reachable_count(), 6);
+
+        let nodes: Vec<_> = (0..200_000).map(Node::new).collect();
+        for w in nodes.windows(2) {
+            add_edge(&w[0], &w[1]);
+        }
+        add_edge(&nodes[199_999], &nodes[0]);
+        assert_eq!(nodes[0].borrow().reachable_count(), 200_000);
+        for n in &nodes {
+            n.borrow_mut().edges.clear();
+        }
+    }
+
+    #[test]
+    fn graph_root_iterates_nodes_depth_first() {
+        let root = GraphRoot(init());
+        let mut v = Vec::new();
+        for n in &root {
+            v.push(n.borrow().datum);
+        }
+        assert_eq!(v, vec!["A", "B", "C", "E", "F", "D"]);
+    }
+
+    #[test]
+    fn graph_root_nodes_and_edges() {
+        let root = GraphRoot(init());
+        assert_eq!(root.nodes().count(), 6);
+        assert_eq!(root.edges().count(), root.0.borrow().edge_count());
+        assert_eq!(root.edges().next(), Some(("A", "B")));
+        assert!(root.edges().any(|e| e == ("C", "A")));
+    }
+
+    #[test]
+    fn degree_and_closeness_centrality() {
+        let g = init();
+        let d = g.borrow().degree_centrality();
+        assert_eq!(d.len(), 6);
+        assert_eq!((d["A"], d["C"], d["B"]), (3, 3, 0));
+        let c = g.borrow().closeness_centrality();
+        assert!((c["A"] - 1.0 / 7.0).abs() < 1e-12);
+        assert!((c["C"] - 1.0 / 7.0).abs() < 1e-12);
+        assert_eq!(c["E"], 0.0);
+        let p = from_adjacency(&[("A", "B"), ("B", "C")]);
+        let c = p.borrow().closeness_centrality();
+        assert!((c["A"] - 1.0 / 3.0).abs() < 1e-12);
+        assert!((c["B"] - 1.0).abs() < 1e-12);
+
+        // Nodes which share a datum are added together.
+        let dup = init().borrow().map(|d| if *d == "C" { "A" } else { *d });
+        assert_eq!(dup.borrow().degree_centrality()["A"], 6);
+        assert!((dup.borrow().closeness_centrality()["A"] - 2.0 / 7.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pagerank_favours_popular_nodes() {
+        let r = init().borrow().pagerank(0.85, 50);
+        assert!((r.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        let star = from_adjacency(&[("A", "H"), ("B", "H"), ("C", "H"),
+                                    ("H", "A"), ("A", "B"), ("B", "C")]);
+        let r = star.borrow().pagerank(0.85, 100);
+        assert!((r.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(r["H"] > r["A"] && r["H"] > r["B"] && r["H"] > r["C"]);
+        assert!((Node::new("X").borrow().pagerank(0.85, 10)["X"] - 1.0).abs() < 1e-12);
+    }
+
+    fn sorted(mut components: Vec<Vec<&'static str>>) -> Vec<Vec<&'static str>> {
+        for c in &mut components {
+            c.sort();
+        }
+        components.sort();
+        components
+    }
+
+    #[test]
+    fn scc_kosaraju_agrees_with_tarjan() {
+        let g = init();
+        let k = g.borrow().scc_kosaraju();
+        assert_eq!(sorted(k.clone()), sorted(g.borrow().strongly_connected_components()));
+        assert_eq!(sorted(k), vec![vec!["A", "C"], vec!["B"], vec!["D"], vec!["E"], vec!["F"]]);
+        let x = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A"),
+                                 ("C", "D"), ("D", "E"), ("E", "D")]);
+        let k = x.borrow().scc_kosaraju();
+        assert_eq!(sorted(k.clone()), sorted(x.borrow().strongly_connected_components()));
+        assert_eq!(k.len(), 2);
+        assert_eq!(k[0].len(), 3);
+    }
+
+    fn sorted_points(g: &Rc<RefCell<Node>>) -> Vec<&'static str> {
+        let mut v: Vec<_> = g.borrow().articulation_points().into_iter().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn articulation_points_disconnect_the_graph() {
+        assert_eq!(sorted_points(&init()), vec!["A", "C"]);
+        let star = from_adjacency(&[("H", "A"), ("H", "B"), ("C", "H")]);
+        assert_eq!(sorted_points(&star), vec!["H"]);
+        let cycle = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A")]);
+        assert!(sorted_points(&cycle).is_empty());
+        let path = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "B")]);
+        assert_eq!(sorted_points(&path), vec!["B"]);
+        let two = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A"),
+                                   ("C", "D"), ("D", "E"), ("E", "C")]);
+        assert_eq!(sorted_points(&two), vec!["C"]);
+    }
+
+    #[test]
+    fn bridges_are_edges_on_no_cycle() {
+        let two = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A"),
+                                   ("C", "D"), ("D", "E"), ("E", "F"), ("F", "D")]);
+        assert_eq!(two.borrow().bridges(), vec![("C", "D")]);
+        let parallel = from_adjacency(&[("A", "B"), ("B", "A"), ("B", "C")]);
+        assert_eq!(parallel.borrow().bridges(), vec![("B", "C")]);
+        let mut b = init().borrow().bridges();
+        b.sort();
+        assert_eq!(b, vec![("A", "B"), ("A", "D"), ("C", "E"), ("C", "F")]);
+        let self_loop = from_adjacency(&[("A", "A"), ("A", "B")]);
+        assert_eq!(self_loop.borrow().bridges(), vec![("A", "B")]);
+        assert!(sorted_points(&self_loop).is_empty());
+    }
+
+    #[test]
+    fn density_is_the_fraction_of_possible_edges() {
+        let chain = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "D"), ("D", "E")]);
+        assert!((chain.borrow().density() - 4.0 / 20.0).abs() < 1e-12);
+        let k3 = from_adjacency(&[("A", "B"), ("A", "C"), ("B", "A"),
+                                  ("B", "C"), ("C", "A"), ("C", "B")]);
+        assert!((k3.borrow().density() - 1.0).abs() < 1e-12);
+        assert_eq!(Node::new("A").borrow().density(), 0.0);
+    }
+
+    fn total_edges(nodes: &[Rc<RefCell<Node<String>>>]) -> usize {
+        adjacency_from(nodes).edges.iter().map(|t| t.len()).sum()
+    }
+
+    fn unlink(nodes: &[Rc<RefCell<Node<String>>>]) {
+        for n in nodes {
+            n.borrow_mut().edges.clear();
+        }
+    }
+
+    #[test]
+    fn random_graph_is_reproducible() {
+        let a = random_graph(50, 0.1, 42);
+        let b = random_graph(50, 0.1, 42);
+        let c = random_graph(50, 0.1, 43);
+        assert_eq!(a.len(), 50);
+        assert_eq!(a[0].borrow().datum, "n0");
+        let (adj_a, adj_b) = (adjacency_from(&a), adjacency_from(&b));
+        assert_eq!((adj_a.data, adj_a.edges), (adj_b.data, adj_b.edges));
+        assert_ne!(total_edges(&a), total_edges(&c));
+        assert!(total_edges(&a) > 150 && total_edges(&a) < 350);
+        assert_eq!(a[0].borrow().node_count(), 50);
+        assert_eq!(total_edges(&random_graph(10, 0.0, 1)), 0);
+        let full = random_graph(10, 1.0, 1);
+        assert_eq!((full[0].borrow().node_count(), full[0].borrow().edge_count()), (10, 90));
+        for g in &[a, b, c, full] {
+            unlink(g);
+        }
+    }
+
+    #[test]
+    fn contract_edge_merges_the_second_node_into_the_first() {
+        let g = init();
+        assert!(contract_edge(&g, "A", "C"));
+        let mut v: Vec<_> = g.borrow().edges.iter().map(|n| n.borrow().datum).collect();
+        v.sort();
+        assert_eq!(v, vec!["B", "D", "E", "F"]);
+        assert!(find(&g, "C").is_none());
+        assert!(!g.borrow().has_cycle());
+        assert!(!contract_edge(&g, "A", "Z"));
+        assert!(!contract_edge(&g, "A", "A"));
+
+        // Edges into Y are redirected to X.
+        let h = from_adjacency(&[("S", "X"), ("S", "Y"), ("X", "Y"),
+                                 ("Y", "Z"), ("Z", "Y"), ("Y", "Y")]);
+        assert!(contract_edge(&h, "X", "Y"));
+        let (labels, m) = h.borrow().to_adjacency_matrix();
+        assert_eq!(labels, vec!["S", "X", "Z"]);
+        assert_eq!(m, vec![vec![false, true, false],
+                           vec![false, false, true],
+                           vec![false, true, false]]);
+        assert_eq!(h.borrow().edge_count(), 4);
+        find(&h, "Z").unwrap().borrow_mut().edges.clear();
+    }
+
+    #[test]
+    fn subgraph_drops_filtered_nodes() {
+        let g = init();
+        let s = g.borrow().subgraph(|d| *d != "D").unwrap();
+        let (labels, m) = s.borrow().to_adjacency_matrix();
+        assert_eq!(labels, vec!["A", "B", "C", "E", "F"]);
+        assert_eq!(s.borrow().edge_count(), 5);
+        assert!(m[0][1] && m[0][2] && m[2][0]);
+        let s = g.borrow().subgraph(|d| *d != "C").unwrap();
+        assert_eq!(s.borrow().to_adjacency_matrix().0, vec!["A", "B", "D"]);
+        assert!(!s.borrow().has_cycle());
+        assert!(g.borrow().subgraph(|d| *d != "A").is_none());
+    }
+
+    fn targets(n: &Rc<RefCell<Node>>) -> Vec<&'static str> {
+        let mut v: Vec<_> = n.borrow().edges.iter().map(|n| n.borrow().datum).collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn merge_unions_nodes_and_edges() {
+        let a = from_adjacency(&[("A", "B"), ("B", "C"), ("A", "X")]);
+        let b = from_adjacency(&[("B", "C"), ("B", "D"), ("Q", "A"), ("A", "B"), ("A", "B")]);
+        let m = merge(&a, &b);
+        assert_eq!(m.borrow().datum, "A");
+        assert_eq!(targets(&m), vec!["B", "X"]);
+        assert_eq!(targets(&find(&m, "B").unwrap()), vec!["C", "D"]);
+        // Q is not reachable from A.
+        assert_eq!(m.borrow().node_count(), 5);
+        assert_eq!(merge(&Node::new("Z"), &Node::new("Z")).borrow().datum, "Z");
+    }
+
+    #[test]
+    fn node_set_starts_empty() {
+        let mut g: NodeSet = NodeSet::default();
+        assert!(g.is_empty());
+        let a = g.add_node("A");
+        let b = g.add_node("B");
+        add_edge(&a, &b);
+        assert_eq!(g.nodes().len(), 2);
+        assert_eq!(a.borrow().node_count(), 2);
+        assert_eq!(b.borrow().node_count(), 1);
+        assert!(Node::isolated("X").borrow().edges.is_empty());
+    }
+
+    #[test]
+    fn iddfs_finds_the_shallowest_depth() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.iddfs("A", 3), Some(0));
+        assert_eq!(g.iddfs("C", 3), Some(1));
+        assert_eq!(g.iddfs("F", 3), Some(2));
+        assert_eq!(g.iddfs("F", 1), None);
+        assert_eq!(g.iddfs("Z", 10), None);
+        // The first path explored is the long one.
+        let h = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "D"), ("D", "T"), ("A", "T")]);
+        assert_eq!(h.borrow().iddfs("T", 5), Some(1));
+    }
+
+    #[test]
+    fn bidirectional_shortest_path_agrees_with_bfs() {
+        let g = init();
+        let rev = ReverseAdjacency::new(slice::from_ref(&g));
+        let f = find(&g, "F").unwrap();
+        let b = find(&g, "B").unwrap();
+        assert_eq!(bidirectional_shortest_path(&g, &f, &rev), Some(vec!["A", "C", "F"]));
+        assert_eq!(bidirectional_shortest_path(&f, &b, &rev), None);
+        assert_eq!(bidirectional_shortest_path(&g, &g, &rev), Some(vec!["A"]));
+
+        let nodes = random_graph(300, 0.01, 7);
+        let rev = ReverseAdjacency::new(&nodes);
+        let mut found = 0;
+        for t in nodes.iter().skip(1).step_by(7) {
+            let bfs = nodes[0].borrow().shortest_path(t.borrow().datum.clone());
+            let bi = bidirectional_shortest_path(&nodes[0], t, &rev);
+            assert_eq!(bfs.as_ref().map(Vec::len), bi.as_ref().map(Vec::len));
+            if let Some(p) = bi {
+                assert_eq!(p[0], "n0");
+                assert_eq!(p.last(), Some(&t.borrow().datum));
+                found += 1;
+            }
+        }
+        assert!(found > 10);
+        unlink(&nodes);
+    }
+
+    #[test]
+    fn structural_hash_matches_equal_graphs() {
+        let g = init();
+        let c = g.borrow().deep_clone();
+        assert_eq!(g.borrow().structural_hash(), c.borrow().structural_hash());
+        remove_edge(&find(&c, "C").unwrap(), "A");
+        assert_ne!(g.borrow().structural_hash(), c.borrow().structural_hash());
+        assert_ne!(Node::new("X").borrow().structural_hash(),
+                   Node::new("Y").borrow().structural_hash());
+        assert_ne!(from_adjacency(&[("A", "B")]).borrow().structural_hash(),
+                   from_adjacency(&[("B", "A")]).borrow().structural_hash());
+        let ab = GraphBuilder::new().edge("A", "B").edge("A", "C").build();
+        let ba = GraphBuilder::new().edge("A", "C").edge("A", "B").build();
+        assert_eq!(ab.borrow().structural_hash(), ba.borrow().structural_hash());
+    }
+
+    #[test]
+    fn hamiltonian_path_visits_every_node_once() {
+        assert_eq!(init().borrow().hamiltonian_path(), None);
+        let g = from_adjacency(&[("A", "B"), ("A", "C"), ("B", "D"), ("C", "B"), ("D", "A")]);
+        assert_eq!(g.borrow().hamiltonian_path(), Some(vec!["A", "C", "B", "D"]));
+        let h = from_adjacency(&[("X", "Y"), ("Z", "X"), ("Y", "Z")]);
+        assert_eq!(h.borrow().hamiltonian_path(), Some(vec!["X", "Y", "Z"]));
+        assert_eq!(Node::new("Q").borrow().hamiltonian_path(), Some(vec!["Q"]));
+    }
+
+    #[test]
+    fn eulerian_kind_checks_degree_balance() {
+        assert_eq!(init().borrow().eulerian_kind(), EulerianKind::None);
+        let c = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A"), ("A", "D"), ("D", "A")]);
+        assert_eq!(c.borrow().eulerian_kind(), EulerianKind::Circuit);
+        let p = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A"), ("A", "D")]);
+        assert_eq!(p.borrow().eulerian_kind(), EulerianKind::Path);
+        let q = from_adjacency(&[("A", "B"), ("B", "C")]);
+        assert_eq!(q.borrow().eulerian_kind(), EulerianKind::Path);
+        let n = from_adjacency(&[("A", "B"), ("A", "C")]);
+        assert_eq!(n.borrow().eulerian_kind(), EulerianKind::None);
+        assert_eq!(Node::new("Z").borrow().eulerian_kind(), EulerianKind::Circuit);
+    }
+
+    #[test]
+    fn greedy_coloring_separates_neighbours() {
+        let g = init();
+        let colors = g.borrow().greedy_coloring();
+        let (labels, m) = g.borrow().to_adjacency_matrix();
+        for (i, row) in m.iter().enumerate() {
+            for (j, &edge) in row.iter().enumerate() {
+                if edge && i != j {
+                    assert_ne!(colors[labels[i]], colors[labels[j]]);
+                }
+            }
+        }
+        assert_eq!(colors.values().max(), Some(&1));
+        let k3 = from_adjacency(&[("A", "B"), ("B", "C"), ("C", "A"), ("A", "A")]);
+        let mut v: Vec<_> = k3.borrow().greedy_coloring().into_values().collect();
+        v.sort();
+        assert_eq!(v, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn next_batch_yields_nodes_in_chunks() {
+        let g = init();
+        let mut it = dfs_iter(&g);
+        let mut all = Vec::new();
+        loop {
+            let batch = it.next_batch(4);
+            if batch.is_empty() {
+                break;
+            }
+            assert!(batch.len() <= 4);
+            all.extend(batch.iter().map(|n| n.borrow().datum));
+        }
+        assert_eq!(all, order(|f| g.borrow().traverse(&f, &mut HashSet::new())));
+        assert!(it.next_batch(3).is_empty());
+    }
+
+    #[test]
+    fn weakly_connected_components_ignores_edge_direction() {
+        let a = from_adjacency(&[("A", "B"), ("B", "C")]);
+        let x = from_adjacency(&[("X", "Y"), ("Z", "Y")]);
+        let w = weakly_connected_components(&[a.clone(), x.clone()]);
+        assert_eq!(w, vec![vec!["A", "B", "C"], vec!["X", "Y"]]);
+        add_edge(&find(&a, "C").unwrap(), &x);
+        let w = weakly_connected_components(&[a, x]);
+        assert_eq!(w, vec![vec!["A", "B", "C", "X", "Y"]]);
+        assert_eq!(weakly_connected_components(&[init()]).len(), 1);
+        assert_eq!(weakly_connected_components(&[Node::new("P"), Node::new("Q")]).len(), 2);
+    }
+
+    #[test]
+    fn retain_edges_keeps_matching_edges() {
+        let g = init();
+        let c = find(&g, "C").unwrap();
+        retain_edges(&g, |_, to| *to != "C");
+        assert!(!g.borrow().can_reach("C"));
+        assert!(!g.borrow().can_reach("E"));
+        assert_eq!(c.borrow().edges.len(), 3);
+        retain_edges(&c, |from, _| *from != "C");
+        assert!(c.borrow().edges.is_empty());
+    }
+
+    #[test]
+    fn classify_distinguishes_trees_dags_and_cycles() {
+        assert_eq!(init().borrow().classify(), GraphClass::Cyclic);
+        let t = from_adjacency(&[("A", "B"), ("A", "C"), ("C", "D"), ("C", "E")]);
+        assert_eq!(t.borrow().classify(), GraphClass::Tree);
+        let d = from_adjacency(&[("A", "B"), ("A", "C"), ("B", "D"), ("C", "D")]);
+        assert_eq!(d.borrow().classify(), GraphClass::Dag);
+        let x = from_adjacency(&[("X", "Y")]);
+        assert_eq!(classify_from(&[t.clone(), x]), GraphClass::Forest);
+        assert_eq!(classify_from(&[t, d.clone()]), GraphClass::Dag);
+        assert_eq!(Node::new("Q").borrow().classify(), GraphClass::Tree);
+        assert_eq!(from_adjacency(&[("A", "A")]).borrow().classify(), GraphClass::Cyclic);
+        assert_eq!(d.borrow().topological_sort().unwrap(), vec!["A", "B", "C", "D"]);
+    }
+
+    fn sorted_set(set: HashSet<&'static str>) -> Vec<&'static str> {
+        let mut v: Vec<_> = set.into_iter().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn descendants_and_ancestors() {
+        let g = init();
+        assert_eq!(sorted_set(descendants(&g, "C")), vec!["A", "B", "D", "E", "F"]);
+        assert_eq!(sorted_set(descendants(&g, "A")), vec!["B", "C", "D", "E", "F"]);
+        assert!(descendants(&g, "E").is_empty());
+        assert!(descendants(&g, "Z").is_empty());
+        assert_eq!(sorted_set(ancestors(&g, "E")), vec!["A", "C"]);
+        assert_eq!(sorted_set(ancestors(&g, "A")), vec!["C"]);
+        assert!(ancestors(&g, "Z").is_empty());
+        let d = from_adjacency(&[("A", "B"), ("B", "C"), ("X", "C")]);
+        assert_eq!(sorted_set(ancestors(&d, "C")), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn to_graphml_writes_balanced_escaped_xml() {
+        let x = init().borrow().to_graphml();
+        assert_eq!(x.matches("<node ").count(), 6);
+        assert_eq!(x.matches("<edge ").count(), 6);
+        assert!(x.contains("<edge source=\"C\" target=\"A\"/>"));
+
+        // Every opened tag is closed, in order.
+        let mut open = Vec::new();
+        let mut rest = &x[x.find("?>").unwrap() + 2..];
+        while let Some(i) = rest.find('<') {
+            let j = i + rest[i..].find('>').unwrap();
+            let tag = &rest[i + 1..j];
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name));
+            } else if !tag.ends_with('/') {
+                open.push(tag.split_whitespace().next().unwrap());
+            }
+            rest = &rest[j + 1..];
+        }
+        assert!(open.is_empty());
+
+        let e = from_adjacency(&[("a<b", "\"q\"&'"), ("a<b", "a<b")]);
+        let x = e.borrow().to_graphml();
+        assert!(x.contains("<node id=\"a&lt;b\"/>"));
+        assert!(x.contains("<node id=\"&quot;q&quot;&amp;&apos;\"/>"));
+    }
+
+    fn sorted_edges(g: Rc<RefCell<Node>>) -> Vec<(&'static str, &'static str)> {
+        let mut e: Vec<_> = GraphRoot(g).edges().collect();
+        e.sort();
+        e
+    }
+
+    #[test]
+    fn transitive_reduction_keeps_reachability() {
+        let g = GraphBuilder::new()
+            .edge("A", "B").edge("B", "C").edge("C", "D")
+            .edge("A", "D").edge("A", "C").edge("B", "D").edge("A", "B")
+            .build();
+        let r = g.borrow().transitive_reduction();
+        assert_eq!(r.borrow().edges.len(), 1);
+        assert_eq!(sorted_edges(r.clone()), vec![("A", "B"), ("B", "C"), ("C", "D")]);
+        for d in &["A", "B", "C", "D"] {
+            assert_eq!(descendants(&g, d), descendants(&r, d));
+        }
+        let diamond = GraphBuilder::new()
+            .edge("A", "B").edge("A", "C").edge("B", "D").edge("C", "D").edge("A", "D")
+            .build();
+        assert_eq!(sorted_edges(diamond.borrow().transitive_reduction()).len(), 4);
+    }
+
+    #[test]
+    fn transitive_closure_adds_an_edge_per_reachable_node() {
+        let g = GraphBuilder::new().edge("A", "B").edge("B", "C").build();
+        assert_eq!(sorted_edges(g.borrow().transitive_closure()),
+                   vec![("A", "B"), ("A", "C"), ("B", "C")]);
+        let g = GraphBuilder::new().edge("A", "B").edge("B", "A").edge("B", "C").build();
+        assert_eq!(sorted_edges(g.borrow().transitive_closure()),
+                   vec![("A", "A"), ("A", "B"), ("A", "C"), ("B", "A"), ("B", "B"), ("B", "C")]);
+    }
+
+    // Records each `enter` as "+datum" and each `leave` as "-datum".
+    struct Recorder(Vec<String>);
+
+    impl Visitor for Recorder {
+        fn enter(&mut self, datum: &'static str) {
+            self.0.push(format!("+{}", datum));
+        }
+
+        fn leave(&mut self, datum: &'static str) {
+            self.0.push(format!("-{}", datum));
+        }
+    }
+
+    #[test]
+    fn accept_brackets_each_subtree() {
+        let mut r = Recorder(Vec::new());
+        init().borrow().accept(&mut r);
+        assert_eq!(r.0.join(" "), "+A +B -B +C +E -E +F -F -C +D -D -A");
+        let g = GraphBuilder::new()
+            .edge("A", "B").edge("A", "C").edge("B", "D").edge("C", "D")
+            .build();
+        let mut r = Recorder(Vec::new());
+        g.borrow().accept(&mut r);
+        assert_eq!(r.0.join(" "), "+A +B +D -D -B +C -C -A");
+    }
+
+    #[test]
+    fn dfs_timestamps_nest_descendants() {
+        let t = init().borrow().dfs_timestamps();
+        assert_eq!((t["A"], t["B"], t["C"]), ((0, 11), (1, 2), (3, 8)));
+        assert_eq!((t["E"], t["F"], t["D"]), ((4, 5), (6, 7), (9, 10)));
+        for x in &["B", "C", "D", "E", "F"] {
+            assert!(t["A"].0 < t[x].0 && t[x].1 < t["A"].1);
+        }
+    }
+
+    #[test]
+    fn classify_edges_labels_every_edge() {
+        let k = init().borrow().classify_edges();
+        assert!(k.contains(&(("C", "A"), EdgeKind::Back)));
+        assert_eq!(k.iter().filter(|e| e.1 == EdgeKind::Tree).count(), 5);
+
+        let g = GraphBuilder::new()
+            .edge("A", "B").edge("B", "C").edge("A", "C")
+            .edge("A", "D").edge("D", "C").edge("D", "D")
+            .build();
+        let b = g.borrow().edges[0].clone();
+        add_edge(&g, &b);
+        assert_eq!(g.borrow().classify_edges(),
+                   vec![(("A", "B"), EdgeKind::Tree),
+                        (("A", "C"), EdgeKind::Forward),
+                        (("A", "D"), EdgeKind::Tree),
+                        (("A", "B"), EdgeKind::Forward),
+                        (("B", "C"), EdgeKind::Tree),
+                        (("D", "C"), EdgeKind::Cross),
+                        (("D", "D"), EdgeKind::Back)]);
+    }
+
+    #[test]
+    fn add_edges_and_compact() {
+        let hub = Node::new(0);
+        let leaves: Vec<_> = (1..100).map(Node::new).collect();
+        add_edge(&hub, &leaves[0]);
+        add_edges(&hub, &leaves[1..]);
+        add_edge(&leaves[3], &hub);
+        add_edge(&leaves[3], &leaves[4]);
+        assert_eq!(hub.borrow().edges.len(), 99);
+        compact(&hub);
+        assert_eq!(hub.borrow().edges.capacity(), 99);
+        assert_eq!(leaves[3].borrow().edges.capacity(), 2);
+        leaves[3].borrow_mut().edges.clear();
+
+        let self_loop = Node::new(7);
+        add_edges(&self_loop, slice::from_ref(&self_loop));
+        compact(&self_loop);
+        assert_eq!(self_loop.borrow().edges.capacity(), 1);
+        self_loop.borrow_mut().edges.clear();
+    }
+
+    #[test]
+    fn frozen_graph_is_shared_across_threads() {
+        let f = init().borrow().freeze();
+        let handles: Vec<_> = (0..4).map(|_| {
+            let f = f.clone();
+            thread::spawn(move || {
+                let out = Mutex::new(String::new());
+                f.traverse(|d| out.lock().unwrap().push_str(d));
+                let n: Vec<_> = f.neighbors(2).iter().map(|&i| *f.datum(i)).collect();
+                (out.into_inner().unwrap(), n, f.len())
+            })
+        }).collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), ("ABCEFD".to_string(), vec!["E", "F", "A"], 6));
+        }
+    }
+
+    #[test]
+    fn diff_reports_changed_nodes_and_edges() {
+        let a = init();
+        let b = a.borrow().deep_clone();
+        assert!(diff(&a, &b).is_empty());
+        let c = find(&b, "C").unwrap();
+        remove_edge(&c, "A");
+        add_edge(&find(&c, "E").unwrap(), &c);
+        assert_eq!(diff(&a, &b), GraphDiff {
+            added_nodes: vec![],
+            removed_nodes: vec![],
+            added_edges: vec![("E", "C")],
+            removed_edges: vec![("C", "A")],
+        });
+
+        let x = GraphBuilder::new().edge("A", "Z").build();
+        let d = diff(&a, &x);
+        assert_eq!(d.added_nodes, vec!["Z"]);
+        assert_eq!(d.removed_nodes, vec!["B", "C", "E", "F", "D"]);
+        assert!(!d.is_empty());
+    }
+
+    #[test]
+    fn betweenness_centrality_favours_bottlenecks() {
+        // Two triangles joined through M, with every edge in both directions.
+        let mut b = GraphBuilder::new();
+        for &(x, y) in &[("A", "B"), ("B", "C"), ("C", "A"), ("C", "M"),
+                         ("M", "D"), ("D", "E"), ("E", "F"), ("F", "D")] {
+            b = b.edge(x, y).edge(y, x);
+        }
+        let c = b.build().borrow().betweenness_centrality();
+        let best = c.iter().max_by(|a, b| a.1.partial_cmp(b.1).unwrap()).unwrap();
+        assert_eq!(*best.0, "M");
+        assert_eq!(c["A"], 0.0);
+
+        let path = GraphBuilder::new().edge("A", "B").edge("B", "C").build();
+        let c = path.borrow().betweenness_centrality();
+        assert_eq!((c["A"], c["B"], c["C"]), (0.0, 1.0, 0.0));
+        let diamond = GraphBuilder::new()
+            .edge("A", "B").edge("A", "C").edge("B", "D").edge("C", "D")
+            .build();
+        let c = diamond.borrow().betweenness_centrality();
+        assert_eq!((c["B"], c["C"]), (0.5, 0.5));
+    }
+
+    #[test]
+    fn remove_edge_unordered_swaps_in_the_last_edge() {
+        let g = init();
+        assert!(remove_edge_unordered(&g, "B"));
+        let e: Vec<_> = g.borrow().edges.iter().map(|n| n.borrow().datum).collect();
+        assert_eq!(e, vec!["D", "C"]);
+        assert!(!remove_edge_unordered(&g, "B"));
+        let n = Node::new("X");
+        add_edge(&n, &n);
+        assert!(remove_edge_unordered(&n, "X"));
+        assert!(n.borrow().edges.is_empty());
+    }
+
+    #[test]
+    fn count_paths_counts_distinct_routes() {
+        let g = GraphBuilder::new()
+            .edge("A", "B").edge("A", "C").edge("B", "D").edge("C", "D").edge("D", "E")
+            .build();
+        assert_eq!(g.borrow().count_paths("D").unwrap(), 2);
+        assert_eq!(g.borrow().count_paths("E").unwrap(), 2);
+        assert_eq!(g.borrow().count_paths("A").unwrap(), 1);
+        assert_eq!(g.borrow().count_paths("Z").unwrap(), 0);
+        let b = g.borrow().edges[0].clone();
+        add_edge(&g, &b);
+        assert_eq!(g.borrow().count_paths("D").unwrap(), 3);
+        assert!(init().borrow().count_paths("B").is_err());
+    }
+
+    #[test]
+    fn clustering_coefficients() {
+        let triangle = GraphBuilder::new().edge("A", "B").edge("B", "C").edge("C", "A").build();
+        assert_eq!(triangle.borrow().local_clustering("A"), 1.0);
+        assert_eq!(triangle.borrow().global_clustering(), 1.0);
+        let star = GraphBuilder::new()
+            .edge("H", "A").edge("H", "B").edge("H", "C").edge("H", "H")
+            .build();
+        assert_eq!(star.borrow().local_clustering("A"), 0.0);
+        assert_eq!(star.borrow().local_clustering("H"), 0.0);
+        assert_eq!(star.borrow().local_clustering("Z"), 0.0);
+        assert_eq!(star.borrow().global_clustering(), 0.0);
+        let s = GraphBuilder::new()
+            .edge("H", "A").edge("H", "B").edge("H", "C").edge("A", "B")
+            .build();
+        assert!((s.borrow().local_clustering("H") - 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn prune_unreachable_frees_unreachable_cycles() {
+        let drops = Rc::new(Cell::new(0));
+        let mut g = NodeSet::default();
+        let a = g.add_node(Counted(1, drops.clone()));
+        let b = g.add_node(Counted(2, drops.clone()));
+        let x = g.add_node(Counted(3, drops.clone()));
+        let y = g.add_node(Counted(4, drops.clone()));
+        add_edge(&a, &b);
+        add_edge(&b, &a);
+        add_edge(&x, &y);
+        add_edge(&y, &x);
+        add_edge(&x, &a);
+        a.borrow_mut().weak_edges.push(Rc::downgrade(&x));
+        drop((x, y));
+        let before = drops.get();
+        g.prune_unreachable(&a);
+        assert_eq!(drops.get() - before, 2);
+        assert!(a.borrow().weak_edges[0].upgrade().is_none());
+        assert_eq!(g.nodes().len(), 2);
+        assert_eq!(b.borrow().edges.len(), 1);
+        a.borrow_mut().edges.clear();
+    }
+
+    #[test]
+    fn validate_reports_suspicious_edges() {
+        let g = init();
+        assert!(g.borrow().validate().is_ok());
+        let c = g.borrow().edges[1].clone();
+        add_edge(&c, &c);
+        let e = c.borrow().edges[0].clone();
+        add_edge(&c, &e);
+        add_edge(&c, &e);
+        g.borrow_mut().weak_edges.push(Rc::downgrade(&Node::new("gone")));
+        assert_eq!(g.borrow().validate().unwrap_err(),
+                   vec!["1 dangling weak edge(s) from A",
+                        "3 edges from C to E",
+                        "1 self-loop(s) on C"]);
+    }
+
+    #[test]
+    fn set_datum_relabels_in_place() {
+        let g = init();
+        let c = g.borrow().edges[1].clone();
+        set_datum(&c, "Z");
+        assert_eq!(order(|f| g.borrow().traverse(&f, &mut HashSet::new())),
+                   vec!["A", "B", "Z", "E", "F", "D"]);
+        set_datum(&c, "B");
+        assert_eq!(order(|f| g.borrow().traverse(&f, &mut HashSet::new())),
+                   vec!["A", "B", "B", "E", "F", "D"]);
+    }
+
+    #[test]
+    fn estimated_bytes_grows_with_edges() {
+        let g = init();
+        let before = g.borrow().estimated_bytes();
+        assert!(before >= 6 * mem::size_of::<Node>() + 6 * mem::size_of::<Rc<RefCell<Node>>>());
+        let x = Node::new("X");
+        for _ in 0..10 {
+            add_edge(&g, &x);
+        }
+        assert!(g.borrow().estimated_bytes() > before);
+        assert_eq!(Node::new("Q").borrow().estimated_bytes(), mem::size_of::<Node>());
+    }
 }
@@ -1,7 +1,10 @@
 
-use std::cell::UnsafeCell;
-use std::collections::HashSet;
+use std::cell::{RefCell, UnsafeCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 use typed_arena::Arena;
+use graph::Graph;
+use rc_graph;
 
 struct Node<'a> {
     datum: &'static str,
@@ -11,7 +14,7 @@ struct Node<'a> {
 impl<'a> Node<'a> {
     fn new<'b>(datum: &'static str, arena: &'b Arena<Node<'b>>) -> &'b Node<'b> {
         arena.alloc(Node {
-            datum: datum,
+            datum,
             edges: UnsafeCell::new(Vec::new()),
         })
     }
@@ -31,13 +34,81 @@ impl<'a> Node<'a> {
         }
     }
 
-    fn first(&'a self) -> &'a Node<'a> {
+    fn traverse_bfs<F>(&'a self, f: &F)
+        where F: Fn(&'static str)
+    {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self);
+        while let Some(n) = queue.pop_front() {
+            if !seen.insert(n.datum) {
+                continue;
+            }
+            f(n.datum);
+            unsafe {
+                queue.extend((&*n.edges.get()).iter());
+            }
+        }
+    }
+
+    fn first(&'a self) -> Option<&'a Node<'a>> {
         unsafe {
-            (*self.edges.get())[0]
+            (&*self.edges.get()).first().copied()
         }
     }
 }
 
+impl<'a> Graph for Node<'a> {
+    type NodeRef = &'a Node<'a>;
+    type Datum = &'static str;
+
+    fn traverse<F>(&self, f: &F)
+        where F: Fn(&&'static str)
+    {
+        Node::traverse(self, &|d| f(&d), &mut HashSet::new());
+    }
+
+    // We can't use `Node::first` since `self` is not borrowed for `'a`, but
+    // the edges themselves are `&'a Node<'a>`.
+    fn first(&self) -> Option<&'a Node<'a>> {
+        unsafe {
+            (&*self.edges.get()).first().copied()
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        let mut seen = HashSet::new();
+        Node::traverse(self, &|_| {}, &mut seen);
+        seen.len()
+    }
+}
+
+// Copies a graph built with `rc_graph`, where it is easy to change, into
+// `arena`, where it is cheap to traverse. Nodes are matched up by address, so
+// sharing and cycles are kept.
+fn into_ref_graph<'a>(root: &Rc<RefCell<rc_graph::Node>>,
+                      arena: &'a Arena<Node<'a>>) -> &'a Node<'a> {
+    copy_rc_node(root, arena, &mut HashMap::new())
+}
+
+fn copy_rc_node<'a>(node: &Rc<RefCell<rc_graph::Node>>,
+                    arena: &'a Arena<Node<'a>>,
+                    copies: &mut HashMap<*const rc_graph::Node, &'a Node<'a>>) -> &'a Node<'a> {
+    if let Some(&copy) = copies.get(&(node.as_ptr() as *const _)) {
+        return copy;
+    }
+    let node = node.borrow();
+    let copy = Node::new(node.datum, arena);
+    copies.insert(&*node, copy);
+    for n in &node.edges {
+        let target = copy_rc_node(n, arena, copies);
+        unsafe {
+            (*copy.edges.get()).push(target);
+        }
+    }
+    copy
+}
+
 fn foo<'a>(node: &'a Node<'a>) {
     println!("foo: {}", node.datum);
 }
@@ -64,9 +135,131 @@ fn init<'a>(arena: &'a Arena<Node<'a>>) ->&'a Node<'a> {
     root
 }
 
+// Builds a graph from `(from, to)` pairs, with one node per distinct label,
+// and returns the node for the first label. All the nodes are allocated in one
+// go, so they share one chunk of the arena rather than it growing as we go.
+//
+// The arena must outlive every node in it, and each node holds references to
+// other nodes for as long as it lives, so the one lifetime `'a` does for the
+// arena, the nodes and the references between them. The returned node can be
+// used for as long as the arena is borrowed.
+//
+// Panics if `edges` is empty.
+fn init_with_capacity<'a>(arena: &'a Arena<Node<'a>>,
+                          edges: &[(&'static str, &'static str)]) -> &'a Node<'a> {
+    let mut index = HashMap::new();
+    let mut labels = Vec::new();
+    for &(from, to) in edges {
+        for datum in [from, to] {
+            index.entry(datum).or_insert_with(|| {
+                labels.push(datum);
+                labels.len() - 1
+            });
+        }
+    }
+
+    let nodes: &'a [Node<'a>] = arena.alloc_extend(labels.iter().map(|&datum| Node {
+        datum,
+        edges: UnsafeCell::new(Vec::new()),
+    }));
+    for &(from, to) in edges {
+        unsafe {
+            (*nodes[index[from]].edges.get()).push(&nodes[index[to]]);
+        }
+    }
+    &nodes[0]
+}
+
 pub fn main() {
     let arena = Arena::new();
     let g = init(&arena);
     g.traverse(&|d| println!("{}", d), &mut HashSet::new());
-    foo(g.first());
+    if let Some(f) = g.first() {
+        foo(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph;
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::ptr;
+
+    #[test]
+    fn first_is_none_without_edges() {
+        let arena = Arena::new();
+        let g = init(&arena);
+        assert_eq!(g.first().unwrap().datum, "B");
+        assert!(Node::new("X", &arena).first().is_none());
+    }
+
+    #[test]
+    fn traverse_bfs_visits_in_breadth_first_order() {
+        let arena = Arena::new();
+        let g = init(&arena);
+        let v = RefCell::new(Vec::new());
+        g.traverse_bfs(&|d| v.borrow_mut().push(d));
+        assert_eq!(v.into_inner(), vec!["A", "B", "C", "D", "E", "F"]);
+    }
+
+    #[test]
+    fn implements_graph() {
+        let arena = Arena::new();
+        assert_eq!(graph::tests::check(init(&arena)), 6);
+    }
+
+    fn edges<'a>(node: &'a Node<'a>) -> Vec<&'a Node<'a>> {
+        unsafe {
+            (&*node.edges.get()).clone()
+        }
+    }
+
+    #[test]
+    fn into_ref_graph_preserves_sharing_and_cycles() {
+        let a = rc_graph::Node::new("A");
+        let b = rc_graph::Node::new("B");
+        let c = rc_graph::Node::new("C");
+        a.borrow_mut().edges.push(b.clone());
+        a.borrow_mut().edges.push(c.clone());
+        c.borrow_mut().edges.push(a.clone());
+        c.borrow_mut().edges.push(b);
+        let arena = Arena::new();
+        let g = into_ref_graph(&a, &arena);
+        let v = RefCell::new(Vec::new());
+        g.traverse(&|d| v.borrow_mut().push(d), &mut HashSet::new());
+        assert_eq!(v.into_inner(), vec!["A", "B", "C"]);
+        assert_eq!(arena.len(), 3);
+        let (gb, gc) = (edges(g)[0], edges(g)[1]);
+        assert!(ptr::eq(edges(gc)[0], g));
+        assert!(ptr::eq(edges(gc)[1], gb));
+        c.borrow_mut().edges.clear();
+    }
+
+    #[test]
+    fn init_with_capacity_builds_large_graphs() {
+        let labels: Vec<&'static str> = (0..500)
+            .map(|i| &*Box::leak(format!("n{}", i).into_boxed_str()))
+            .collect();
+        let mut edges = Vec::new();
+        for i in 0..500 {
+            edges.push((labels[i], labels[(i + 1) % 500]));
+            edges.push((labels[i], labels[(i * 7) % 500]));
+        }
+        assert_eq!(edges.len(), 1000);
+        let arena = Arena::new();
+        let g = init_with_capacity(&arena, &edges);
+        assert_eq!(g.datum, "n0");
+        assert_eq!(arena.len(), 500);
+        let count = Cell::new(0);
+        g.traverse(&|_| count.set(count.get() + 1), &mut HashSet::new());
+        assert_eq!(count.get(), 500);
+
+        let arena = Arena::new();
+        let g = init_with_capacity(&arena, &[("A", "B"), ("A", "C"), ("C", "A")]);
+        let v = RefCell::new(Vec::new());
+        g.traverse(&|d| v.borrow_mut().push(d), &mut HashSet::new());
+        assert_eq!(v.into_inner(), vec!["A", "B", "C"]);
+    }
 }
@@ -1,83 +1,144 @@
-// Note that this one is hypothetical future Rust and will not compile today.
+// Generic modules don't exist, but we can get most of the way there today by
+// putting the arena in a struct which is parameterised with the lifetime of the
+// graph. That lifetime is named once, on `Graph`, and everything reached
+// through a `&'a Graph<'a>` shares it.
 
-use std::cell::UnsafeCell;
+use std::cell::{Cell, UnsafeCell};
 use std::collections::HashSet;
-use arena::TypedArena;
-
-// Module is parameterised with the lifetime of the graph.
-mod graph<'a> {
-    struct Node {
-        datum: &'static str,
-        // The module-level lifetime is used for the lifetime of each Node.
-        edges: UnsafeCell<Vec<&'a Node>>,
+use typed_arena::Arena;
+
+struct Node<'a> {
+    datum: &'static str,
+    // The graph's lifetime is used for the lifetime of each Node.
+    edges: UnsafeCell<Vec<&'a Node<'a>>>,
+}
+
+// Owns every node in the graph, so nodes live exactly as long as the `Graph`.
+struct Graph<'a> {
+    arena: Arena<Node<'a>>,
+    root: Cell<Option<&'a Node<'a>>>,
+}
+
+impl<'a> Node<'a> {
+    fn traverse<F>(&self, f: &F, seen: &mut HashSet<&'static str>)
+        where F: Fn(&'static str)
+    {
+        if seen.contains(&self.datum) {
+            return;
+        }
+        f(self.datum);
+        seen.insert(self.datum);
+        unsafe {
+            for n in &(*self.edges.get()) {
+                n.traverse(f, seen);
+            }
+        }
     }
 
-    impl Node {
-        fn new(datum: &'static str, arena: &'a TypedArena<Node>) -> &'a Node {
-            arena.alloc(Node {
-                datum: datum,
-                edges: UnsafeCell::new(Vec::new()),
-            })
+    fn first(&self) -> Option<&'a Node<'a>> {
+        unsafe {
+            (&*self.edges.get()).first().copied()
         }
+    }
 
-        fn traverse<F>(&self, f: &F, seen: &mut HashSet<&'static str>)
-            where F: Fn(&'static str)
-        {
-            if seen.contains(&self.datum) {
-                return;
-            }
-            f(self.datum);
-            seen.insert(self.datum);
-            for n in &self.edges {
-                unsafe {
-                    for n in &(*self.edges.get()) {
-                        n.traverse(f, seen);
-                    }
-                }
-            }
+    fn add_edge(&self, to: &'a Node<'a>) {
+        unsafe {
+            (*self.edges.get()).push(to);
         }
+    }
+}
 
-        fn first(&self) -> &Node {
-            unsafe {
-                (*self.edges.get())[0]
-            }
+impl<'a> Graph<'a> {
+    fn new() -> Graph<'a> {
+        Graph {
+            arena: Arena::new(),
+            root: Cell::new(None),
         }
     }
 
-    // It would be nice if we could rely on lifetime elision and remove the `'a`
-    // on the `foo` and `init` functions.
-    fn foo(node: &'a Node) {
-        println!("foo: {}", node.datum);
+    // The first node added becomes the root of the graph.
+    fn add_node(&'a self, datum: &'static str) -> &'a Node<'a> {
+        let node = self.arena.alloc(Node {
+            datum,
+            edges: UnsafeCell::new(Vec::new()),
+        });
+        if self.root.get().is_none() {
+            self.root.set(Some(node));
+        }
+        node
     }
 
-    fn init(arena: &'a TypedArena<Node>) -> &'a Node {
-        let root = Node::new("A", arena);
+    // Traverse the nodes reachable from the root.
+    fn traverse<F>(&'a self, f: &F)
+        where F: Fn(&'static str)
+    {
+        if let Some(root) = self.root.get() {
+            root.traverse(f, &mut HashSet::new());
+        }
+    }
+}
 
-        let b = Node::new("B", arena);
-        let c = Node::new("C", arena);
-        let d = Node::new("D", arena);
-        let e = Node::new("E", arena);
-        let f = Node::new("F", arena);
+// These still need the lifetime, but only because they are free functions; as
+// methods on `Graph` they would pick it up from the impl.
+fn foo<'a>(node: &'a Node<'a>) {
+    println!("foo: {}", node.datum);
+}
 
-        unsafe {
-            (*root.edges.get()).push(b);
-            (*root.edges.get()).push(c);
-            (*root.edges.get()).push(d);
+fn init<'a>(g: &'a Graph<'a>) -> &'a Node<'a> {
+    let root = g.add_node("A");
 
-            (*c.edges.get()).push(e);
-            (*c.edges.get()).push(f);
-            (*c.edges.get()).push(root);
-        }
+    let b = g.add_node("B");
+    let c = g.add_node("C");
+    let d = g.add_node("D");
+    let e = g.add_node("E");
+    let f = g.add_node("F");
 
-        root
-    }
+    root.add_edge(b);
+    root.add_edge(c);
+    root.add_edge(d);
+
+    c.add_edge(e);
+    c.add_edge(f);
+    c.add_edge(root);
+
+    root
 }
 
 pub fn main() {
-    let arena = TypedArena::new();
-    // The lifetime of the module is inferred here from the lifetime of the
-    // reference to the arena, i.e., the scope of the main function.
-    let g = graph::init(&arena);
-    g.traverse(&|d| println!("{}", d), &mut HashSet::new());
-    foo(g.first());
+    // The lifetime of the graph is inferred here from the lifetime of the
+    // borrow of `g`, i.e., the scope of the main function.
+    let g = Graph::new();
+    let root = init(&g);
+    g.traverse(&|d| println!("{}", d));
+    if let Some(f) = root.first() {
+        foo(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::cell::RefCell;
+
+    #[test]
+    fn traverse_visits_each_node_once() {
+        let g = Graph::new();
+        init(&g);
+        let count = Cell::new(0);
+        g.traverse(&|_| count.set(count.get() + 1));
+        assert_eq!(count.get(), 6);
+    }
+
+    #[test]
+    fn first_node_added_is_the_root() {
+        let g = Graph::new();
+        assert_eq!(g.add_node("X").datum, "X");
+        let y = g.add_node("Y");
+        assert!(y.first().is_none());
+        y.add_edge(y);
+        let v = RefCell::new(Vec::new());
+        g.traverse(&|d| v.borrow_mut().push(d));
+        assert_eq!(v.into_inner(), vec!["X"]);
+    }
 }
@@ -0,0 +1,88 @@
+// The same as `ref_graph`, but using `RefCell` rather than `UnsafeCell`, so we
+// get dynamic checks instead of `unsafe` blocks.
+#![forbid(unsafe_code)]
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use typed_arena::Arena;
+
+struct Node<'a> {
+    datum: &'static str,
+    edges: RefCell<Vec<&'a Node<'a>>>,
+}
+
+impl<'a> Node<'a> {
+    fn new<'b>(datum: &'static str, arena: &'b Arena<Node<'b>>) -> &'b Node<'b> {
+        arena.alloc(Node {
+            datum,
+            edges: RefCell::new(Vec::new()),
+        })
+    }
+
+    fn traverse<F>(&self, f: &F, seen: &mut HashSet<&'static str>)
+        where F: Fn(&'static str)
+    {
+        if seen.contains(&self.datum) {
+            return;
+        }
+        f(self.datum);
+        seen.insert(self.datum);
+        for n in self.edges.borrow().iter() {
+            n.traverse(f, seen);
+        }
+    }
+
+    fn first(&'a self) -> Option<&'a Node<'a>> {
+        self.edges.borrow().first().copied()
+    }
+}
+
+fn foo<'a>(node: &'a Node<'a>) {
+    println!("foo: {}", node.datum);
+}
+
+fn init<'a>(arena: &'a Arena<Node<'a>>) -> &'a Node<'a> {
+    let root = Node::new("A", arena);
+
+    let b = Node::new("B", arena);
+    let c = Node::new("C", arena);
+    let d = Node::new("D", arena);
+    let e = Node::new("E", arena);
+    let f = Node::new("F", arena);
+
+    root.edges.borrow_mut().push(b);
+    root.edges.borrow_mut().push(c);
+    root.edges.borrow_mut().push(d);
+
+    c.edges.borrow_mut().push(e);
+    c.edges.borrow_mut().push(f);
+    c.edges.borrow_mut().push(root);
+
+    root
+}
+
+pub fn main() {
+    let arena = Arena::new();
+    let g = init(&arena);
+    g.traverse(&|d| println!("{}", d), &mut HashSet::new());
+    if let Some(f) = g.first() {
+        foo(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn traverse_visits_each_node_once() {
+        let arena = Arena::new();
+        let g = init(&arena);
+        let count = Cell::new(0);
+        g.traverse(&|_| count.set(count.get() + 1), &mut HashSet::new());
+        assert_eq!(count.get(), 6);
+        assert_eq!(g.first().unwrap().datum, "B");
+        assert!(Node::new("X", &arena).first().is_none());
+    }
+}
@@ -0,0 +1,145 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+struct Node {
+    datum: &'static str,
+    edges: Vec<Rc<RefCell<Node>>>,
+}
+
+// Every edge is stored in both of its endpoints, so every edge is also an `Rc`
+// cycle. The graph keeps hold of all its nodes so it can break those cycles
+// when it is dropped.
+struct UndirectedGraph {
+    nodes: Vec<Rc<RefCell<Node>>>,
+}
+
+impl Node {
+    fn traverse<F>(&self, f: &F, seen: &mut HashSet<&'static str>)
+        where F: Fn(&'static str)
+    {
+        if seen.contains(&self.datum) {
+            return;
+        }
+        f(self.datum);
+        seen.insert(self.datum);
+        for n in &self.edges {
+            n.borrow().traverse(f, seen);
+        }
+    }
+
+    fn has_edge(&self, to: &Rc<RefCell<Node>>) -> bool {
+        self.edges.iter().any(|n| Rc::ptr_eq(n, to))
+    }
+}
+
+impl UndirectedGraph {
+    fn new() -> UndirectedGraph {
+        UndirectedGraph { nodes: Vec::new() }
+    }
+
+    fn add_node(&mut self, datum: &'static str) -> Rc<RefCell<Node>> {
+        let node = Rc::new(RefCell::new(Node {
+            datum,
+            edges: Vec::new(),
+        }));
+        self.nodes.push(node.clone());
+        node
+    }
+
+    // Connects `a` and `b` in both directions. Does nothing if they are already
+    // connected, and a loop from a node to itself is only stored once.
+    fn add_edge(&self, a: &Rc<RefCell<Node>>, b: &Rc<RefCell<Node>>) {
+        if a.borrow().has_edge(b) {
+            return;
+        }
+        a.borrow_mut().edges.push(b.clone());
+        if !Rc::ptr_eq(a, b) {
+            b.borrow_mut().edges.push(a.clone());
+        }
+    }
+
+    // The number of edges touching `node`.
+    fn degree(&self, node: &Rc<RefCell<Node>>) -> usize {
+        node.borrow().edges.len()
+    }
+
+    // The number of distinct edges, each counted once however it is stored.
+    fn edge_count(&self) -> usize {
+        let ends: usize = self.nodes.iter().map(|n| self.degree(n)).sum();
+        let loops = self.nodes.iter().filter(|n| n.borrow().has_edge(n)).count();
+        (ends + loops) / 2
+    }
+}
+
+impl Drop for UndirectedGraph {
+    fn drop(&mut self) {
+        for n in &self.nodes {
+            n.borrow_mut().edges.clear();
+        }
+    }
+}
+
+fn init() -> (UndirectedGraph, Rc<RefCell<Node>>) {
+    let mut g = UndirectedGraph::new();
+    let root = g.add_node("A");
+
+    let b = g.add_node("B");
+    let c = g.add_node("C");
+    let d = g.add_node("D");
+    let e = g.add_node("E");
+
+    g.add_edge(&root, &b);
+    g.add_edge(&root, &c);
+    g.add_edge(&c, &d);
+    g.add_edge(&d, &e);
+    // Already there, from the other side.
+    g.add_edge(&c, &root);
+
+    (g, root)
+}
+
+pub fn main() {
+    let (g, root) = init();
+    root.borrow().traverse(&|d| println!("{}", d), &mut HashSet::new());
+    println!("degree of A: {}, edges: {}", g.degree(&root), g.edge_count());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn order(n: &Rc<RefCell<Node>>) -> Vec<&'static str> {
+        let v = RefCell::new(Vec::new());
+        n.borrow().traverse(&|d| v.borrow_mut().push(d), &mut HashSet::new());
+        v.into_inner()
+    }
+
+    #[test]
+    fn add_edge_links_both_ends() {
+        let mut g = UndirectedGraph::new();
+        let a = g.add_node("A");
+        let b = g.add_node("B");
+        g.add_edge(&a, &b);
+        g.add_edge(&b, &a);
+        assert_eq!(order(&a), vec!["A", "B"]);
+        assert_eq!(order(&b), vec!["B", "A"]);
+        assert_eq!((g.degree(&a), g.degree(&b), g.edge_count()), (1, 1, 1));
+        g.add_edge(&a, &a);
+        assert_eq!((g.degree(&a), g.edge_count()), (2, 2));
+
+        // Dropping the graph breaks the reference cycles between neighbours.
+        let w = Rc::downgrade(&a);
+        drop((a, b, g));
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn sample_graph() {
+        let (g, root) = init();
+        assert_eq!(order(&root), vec!["A", "B", "C", "D", "E"]);
+        assert_eq!(g.degree(&root), 2);
+        assert_eq!(g.edge_count(), 4);
+    }
+}
@@ -0,0 +1,549 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+// Weights default to `u32`, which is what `dijkstra` and friends need. The
+// algorithms which allow negative weights work with anything which converts to
+// an `i64`.
+struct Node<W = u32> {
+    datum: &'static str,
+    // Each edge carries the cost of following it.
+    edges: Vec<(W, Rc<RefCell<Node<W>>>)>,
+}
+
+// The graph reachable from a node, flattened so that nodes can be referred to
+// by index. The start node is always index 0.
+struct Adjacency<W> {
+    data: Vec<&'static str>,
+    edges: Vec<Vec<(W, usize)>>,
+}
+
+impl<W> Adjacency<W> {
+    // Follows `pred` back from `i` to the start node.
+    fn path_to(&self, mut i: usize, pred: &[usize]) -> Vec<&'static str> {
+        let mut path = vec![self.data[i]];
+        while i != 0 {
+            i = pred[i];
+            path.push(self.data[i]);
+        }
+        path.reverse();
+        path
+    }
+
+    // Kahn's algorithm, ignoring weights. `None` if there is a cycle.
+    fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut in_degree = vec![0; self.data.len()];
+        for targets in &self.edges {
+            for &(_, t) in targets {
+                in_degree[t] += 1;
+            }
+        }
+        let mut ready: VecDeque<_> = (0..self.data.len()).filter(|&i| in_degree[i] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(i) = ready.pop_front() {
+            order.push(i);
+            for &(_, t) in &self.edges[i] {
+                in_degree[t] -= 1;
+                if in_degree[t] == 0 {
+                    ready.push_back(t);
+                }
+            }
+        }
+        if order.len() < self.data.len() {
+            return None;
+        }
+        Some(order)
+    }
+}
+
+impl Adjacency<u32> {
+    // Dijkstra's algorithm from `start` to `target`, not going through any
+    // node marked in `banned_nodes` or along any edge in `banned_edges`.
+    // Returns the cost and the nodes on the path, including both ends.
+    fn cheapest_path(&self,
+                     start: usize,
+                     target: usize,
+                     banned_nodes: &[bool],
+                     banned_edges: &HashSet<(usize, usize)>)
+                     -> Option<(u32, Vec<usize>)> {
+        let mut dist = vec![None; self.data.len()];
+        let mut pred = vec![start; self.data.len()];
+        let mut heap = BinaryHeap::new();
+        dist[start] = Some(0);
+        heap.push(Reverse((0, start)));
+        while let Some(Reverse((cost, i))) = heap.pop() {
+            // A cheaper route to `i` was found after this entry was pushed.
+            if dist[i] < Some(cost) {
+                continue;
+            }
+            if i == target {
+                let mut path = vec![i];
+                let mut i = i;
+                while i != start {
+                    i = pred[i];
+                    path.push(i);
+                }
+                path.reverse();
+                return Some((cost, path));
+            }
+            for &(weight, t) in &self.edges[i] {
+                if banned_nodes[t] || banned_edges.contains(&(i, t)) {
+                    continue;
+                }
+                let next = cost + weight;
+                if dist[t].is_none_or(|d| next < d) {
+                    dist[t] = Some(next);
+                    pred[t] = i;
+                    heap.push(Reverse((next, t)));
+                }
+            }
+        }
+        None
+    }
+
+    // The cost of following `path`, taking the cheapest of any parallel edges.
+    fn path_cost(&self, path: &[usize]) -> u32 {
+        path.windows(2)
+            .map(|w| self.edges[w[0]].iter().filter(|e| e.1 == w[1]).map(|e| e.0).min().unwrap())
+            .sum()
+    }
+}
+
+#[derive(Debug)]
+struct NegativeCycleError;
+
+impl fmt::Display for NegativeCycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "graph contains a negative cycle")
+    }
+}
+
+impl Error for NegativeCycleError {}
+
+impl<W: Copy> Node<W> {
+    fn new(datum: &'static str) -> Rc<RefCell<Node<W>>> {
+        Rc::new(RefCell::new(Node {
+            datum,
+            edges: Vec::new(),
+        }))
+    }
+
+    fn adjacency(&self) -> Adjacency<W> {
+        let mut data = Vec::new();
+        let mut targets = Vec::new();
+        self.collect(&mut HashSet::new(), &mut data, &mut targets);
+
+        let index: HashMap<_, _> = data.iter().enumerate().map(|(i, &d)| (d, i)).collect();
+        let edges = targets.iter()
+            .map(|ts: &Vec<(W, &'static str)>| ts.iter().map(|&(w, t)| (w, index[t])).collect())
+            .collect();
+        Adjacency { data, edges }
+    }
+
+    fn collect(&self,
+               seen: &mut HashSet<&'static str>,
+               data: &mut Vec<&'static str>,
+               targets: &mut Vec<Vec<(W, &'static str)>>) {
+        if !seen.insert(self.datum) {
+            return;
+        }
+        data.push(self.datum);
+        targets.push(self.edges.iter().map(|&(w, ref n)| (w, n.borrow().datum)).collect());
+        for (_, n) in &self.edges {
+            n.borrow().collect(seen, data, targets);
+        }
+    }
+}
+
+impl<W: Copy + Into<i64>> Node<W> {
+    // Like `dijkstra`, but edges may have negative weights. Relaxes every edge
+    // once per node; if an edge can still be relaxed after that, there is a
+    // negative cycle reachable from `self` and no path is cheapest.
+    fn bellman_ford(&self, target: &'static str)
+                    -> Result<Option<(i64, Vec<&'static str>)>, NegativeCycleError> {
+        let adj = self.adjacency();
+        let mut dist: Vec<Option<i64>> = vec![None; adj.data.len()];
+        let mut pred = vec![0; adj.data.len()];
+        dist[0] = Some(0);
+        for _ in 1..adj.data.len() {
+            for (from, targets) in adj.edges.iter().enumerate() {
+                let Some(cost) = dist[from] else { continue };
+                for &(weight, to) in targets {
+                    let next = cost + weight.into();
+                    if dist[to].is_none_or(|d| next < d) {
+                        dist[to] = Some(next);
+                        pred[to] = from;
+                    }
+                }
+            }
+        }
+
+        for (from, targets) in adj.edges.iter().enumerate() {
+            let Some(cost) = dist[from] else { continue };
+            for &(weight, to) in targets {
+                if dist[to].is_none_or(|d| cost + weight.into() < d) {
+                    return Err(NegativeCycleError);
+                }
+            }
+        }
+
+        Ok(adj.data.iter()
+            .position(|&d| d == target)
+            .and_then(|i| dist[i].map(|cost| (cost, adj.path_to(i, &pred)))))
+    }
+
+    // The most expensive path from `self` to `target`, e.g., the critical path
+    // through a schedule where edges are tasks weighted by how long they take.
+    // Taking the nodes in topological order, the most expensive way to reach
+    // each node is known before any edge out of it is followed. `None` if
+    // there is a cycle, which could be followed forever, or if `target` can't
+    // be reached.
+    fn longest_path(&self, target: &'static str) -> Option<(i64, Vec<&'static str>)> {
+        let adj = self.adjacency();
+        let order = adj.topological_order()?;
+        let mut dist: Vec<Option<i64>> = vec![None; adj.data.len()];
+        let mut pred = vec![0; adj.data.len()];
+        dist[0] = Some(0);
+        for i in order {
+            let Some(cost) = dist[i] else { continue };
+            for &(weight, to) in &adj.edges[i] {
+                let next = cost + weight.into();
+                if dist[to].is_none_or(|d| next > d) {
+                    dist[to] = Some(next);
+                    pred[to] = i;
+                }
+            }
+        }
+        let i = adj.data.iter().position(|&d| d == target)?;
+        dist[i].map(|cost| (cost, adj.path_to(i, &pred)))
+    }
+
+    // Floyd-Warshall: the cost of the cheapest path between every pair of
+    // nodes reachable from `self`, with `None` where there is no path. Nodes
+    // are in the order of the first vector, which starts with `self`. If there
+    // is a negative cycle, some nodes will have a negative distance to
+    // themselves.
+    fn all_pairs_shortest_paths(&self) -> (Vec<&'static str>, Vec<Vec<Option<i64>>>) {
+        let adj = self.adjacency();
+        let n = adj.data.len();
+        let mut dist = vec![vec![None; n]; n];
+        for (from, targets) in adj.edges.iter().enumerate() {
+            dist[from][from] = Some(0);
+            for &(weight, to) in targets {
+                let weight = weight.into();
+                if dist[from][to].is_none_or(|d| weight < d) {
+                    dist[from][to] = Some(weight);
+                }
+            }
+        }
+
+        // Try going via each node `k` in turn.
+        for k in 0..n {
+            let via = dist[k].clone();
+            for row in &mut dist {
+                let Some(ik) = row[k] else { continue };
+                for (ij, kj) in row.iter_mut().zip(&via) {
+                    let Some(kj) = *kj else { continue };
+                    if ij.is_none_or(|d| ik + kj < d) {
+                        *ij = Some(ik + kj);
+                    }
+                }
+            }
+        }
+        (adj.data, dist)
+    }
+}
+
+impl Node {
+    // Returns the cost of the cheapest path from `self` to `target` and the
+    // path itself.
+    fn dijkstra(&self, target: &'static str) -> Option<(u32, Vec<&'static str>)> {
+        let adj = self.adjacency();
+        let target = adj.data.iter().position(|&d| d == target)?;
+        let banned_nodes = vec![false; adj.data.len()];
+        let (cost, path) = adj.cheapest_path(0, target, &banned_nodes, &HashSet::new())?;
+        Some((cost, path.iter().map(|&i| adj.data[i]).collect()))
+    }
+
+    // Yen's algorithm: the `k` cheapest paths from `self` to `target` which
+    // don't visit any node twice, cheapest first. There may be fewer than `k`
+    // such paths. Each path after the first is found by taking a prefix of an
+    // earlier path and finding the cheapest way on from there, using
+    // `dijkstra`'s machinery, which doesn't go back through the prefix or
+    // leave it by a way an earlier path already has.
+    fn k_shortest_paths(&self, target: &'static str, k: usize) -> Vec<(u32, Vec<&'static str>)> {
+        let adj = self.adjacency();
+        let n = adj.data.len();
+        let Some(target) = adj.data.iter().position(|&d| d == target) else {
+            return Vec::new();
+        };
+        let mut found: Vec<(u32, Vec<usize>)> = Vec::new();
+        let mut candidates = BinaryHeap::new();
+        let mut seen = HashSet::new();
+        if k > 0 {
+            candidates.extend(adj.cheapest_path(0, target, &vec![false; n], &HashSet::new())
+                .map(Reverse));
+        }
+        while let Some(Reverse((cost, path))) = candidates.pop() {
+            found.push((cost, path));
+            if found.len() == k {
+                break;
+            }
+            let last = &found[found.len() - 1].1;
+            for spur in 0..last.len() - 1 {
+                let prefix = &last[..=spur];
+                let mut banned_nodes = vec![false; n];
+                for &i in &prefix[..spur] {
+                    banned_nodes[i] = true;
+                }
+                let banned_edges = found.iter()
+                    .filter(|(_, p)| p.len() > spur + 1 && p[..=spur] == *prefix)
+                    .map(|(_, p)| (p[spur], p[spur + 1]))
+                    .collect();
+                let Some((spur_cost, rest)) =
+                    adj.cheapest_path(last[spur], target, &banned_nodes, &banned_edges)
+                else {
+                    continue;
+                };
+                let mut path = prefix[..spur].to_vec();
+                path.extend(rest);
+                if seen.insert(path.clone()) {
+                    candidates.push(Reverse((adj.path_cost(&path[..=spur]) + spur_cost, path)));
+                }
+            }
+        }
+        found.into_iter()
+            .map(|(cost, path)| (cost, path.iter().map(|&i| adj.data[i]).collect()))
+            .collect()
+    }
+
+    // Like `dijkstra`, but nodes are explored in order of their cost so far
+    // plus `heuristic`'s estimate of the remaining cost to `target`. The path
+    // found is the cheapest one as long as `heuristic` never overestimates. A
+    // heuristic which is always zero makes this the same as `dijkstra`.
+    fn astar<H>(&self, target: &'static str, heuristic: H) -> Option<(u32, Vec<&'static str>)>
+        where H: Fn(&'static str) -> u32
+    {
+        let adj = self.adjacency();
+        let mut dist = vec![None; adj.data.len()];
+        let mut pred = vec![0; adj.data.len()];
+        let mut heap = BinaryHeap::new();
+        dist[0] = Some(0);
+        heap.push(Reverse((heuristic(adj.data[0]), 0, 0)));
+        while let Some(Reverse((_, cost, i))) = heap.pop() {
+            if dist[i] < Some(cost) {
+                continue;
+            }
+            if adj.data[i] == target {
+                return Some((cost, adj.path_to(i, &pred)));
+            }
+            for &(weight, t) in &adj.edges[i] {
+                let next = cost + weight;
+                if dist[t].is_none_or(|d| next < d) {
+                    dist[t] = Some(next);
+                    pred[t] = i;
+                    heap.push(Reverse((next + heuristic(adj.data[t]), next, t)));
+                }
+            }
+        }
+        None
+    }
+
+    // Picks the cheapest set of edges connecting every node reachable from
+    // `self`, using Prim's algorithm. Edges are treated as undirected here, so
+    // `A -> B` can connect `B` to the tree as well as the other way round.
+    // Returns the chosen edges, in the order they were added, with their
+    // weights.
+    fn minimum_spanning_tree(&self) -> Vec<((&'static str, &'static str), u32)> {
+        let adj = self.adjacency();
+        let mut neighbors = adj.edges.clone();
+        for (from, targets) in adj.edges.iter().enumerate() {
+            for &(weight, to) in targets {
+                neighbors[to].push((weight, from));
+            }
+        }
+
+        let mut in_tree = vec![false; adj.data.len()];
+        let mut tree = Vec::new();
+        let mut heap = BinaryHeap::new();
+        in_tree[0] = true;
+        for &(weight, to) in &neighbors[0] {
+            heap.push(Reverse((weight, 0, to)));
+        }
+        while let Some(Reverse((weight, from, to))) = heap.pop() {
+            if in_tree[to] {
+                continue;
+            }
+            in_tree[to] = true;
+            tree.push(((adj.data[from], adj.data[to]), weight));
+            for &(w, next) in &neighbors[to] {
+                if !in_tree[next] {
+                    heap.push(Reverse((w, to, next)));
+                }
+            }
+        }
+        tree
+    }
+}
+
+fn init() -> Rc<RefCell<Node>> {
+    let root = Node::new("A");
+
+    let b = Node::new("B");
+    let c = Node::new("C");
+    let d = Node::new("D");
+    let e = Node::new("E");
+
+    // A -> C -> E has fewer edges, but A -> B -> D -> E is cheaper.
+    root.borrow_mut().edges.push((1, b.clone()));
+    root.borrow_mut().edges.push((5, c.clone()));
+    b.borrow_mut().edges.push((2, d.clone()));
+    c.borrow_mut().edges.push((5, e.clone()));
+    d.borrow_mut().edges.push((3, e.clone()));
+    e.borrow_mut().edges.push((1, root.clone()));
+
+    root
+}
+
+pub fn main() {
+    let g = init();
+    let g = g.borrow();
+    if let Some((cost, path)) = g.dijkstra("E") {
+        println!("{} (cost {})", path.join(" -> "), cost);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dijkstra_prefers_cheaper_paths() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.dijkstra("E"), Some((6, vec!["A", "B", "D", "E"])));
+        assert_eq!(g.dijkstra("A"), Some((0, vec!["A"])));
+        assert_eq!(g.dijkstra("Z"), None);
+    }
+
+    #[test]
+    fn minimum_spanning_tree_picks_cheapest_edges() {
+        let t = init().borrow().minimum_spanning_tree();
+        assert_eq!(t.len(), 4);
+        assert_eq!(t.iter().map(|e| e.1).sum::<u32>(), 9);
+        assert_eq!(&t[..3], &[(("A", "B"), 1), (("A", "E"), 1), (("B", "D"), 2)]);
+        assert!(Node::new("X").borrow().minimum_spanning_tree().is_empty());
+    }
+
+    #[test]
+    fn astar_agrees_with_dijkstra() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.astar("E", |_| 0), g.dijkstra("E"));
+        // The exact remaining cost to E.
+        let exact = |d| match d { "A" => 6, "B" => 5, "C" => 5, "D" => 3, _ => 0 };
+        assert_eq!(g.astar("E", exact), Some((6, vec!["A", "B", "D", "E"])));
+        // Admissible, but makes C look more promising than B.
+        let skewed = |d| match d { "B" => 5, _ => 0 };
+        assert_eq!(g.astar("E", skewed), Some((6, vec!["A", "B", "D", "E"])));
+        assert_eq!(g.astar("Z", |_| 0), None);
+        assert_eq!(g.astar("A", |_| 0), Some((0, vec!["A"])));
+    }
+
+    #[test]
+    fn bellman_ford_handles_negative_edges() {
+        let g = init();
+        assert_eq!(g.borrow().bellman_ford("E").unwrap(), Some((6, vec!["A", "B", "D", "E"])));
+
+        // The negative edge makes A -> C -> E cheaper.
+        let a: Rc<RefCell<Node<i64>>> = Node::new("A");
+        let b = Node::new("B");
+        let c = Node::new("C");
+        let e = Node::new("E");
+        a.borrow_mut().edges.push((1, b.clone()));
+        a.borrow_mut().edges.push((5, c.clone()));
+        b.borrow_mut().edges.push((5, e.clone()));
+        c.borrow_mut().edges.push((-4, e.clone()));
+        assert_eq!(a.borrow().bellman_ford("E").unwrap(), Some((1, vec!["A", "C", "E"])));
+        assert_eq!(a.borrow().bellman_ford("Z").unwrap(), None);
+
+        e.borrow_mut().edges.push((-3, c));
+        let err = a.borrow().bellman_ford("E").unwrap_err();
+        assert_eq!(err.to_string(), "graph contains a negative cycle");
+        e.borrow_mut().edges.clear();
+    }
+
+    #[test]
+    fn all_pairs_shortest_paths_allows_negative_edges() {
+        let a: Rc<RefCell<Node<i64>>> = Node::new("A");
+        let b = Node::new("B");
+        let c = Node::new("C");
+        let d = Node::new("D");
+        a.borrow_mut().edges.push((4, b.clone()));
+        a.borrow_mut().edges.push((1, c.clone()));
+        c.borrow_mut().edges.push((2, b.clone()));
+        b.borrow_mut().edges.push((1, d.clone()));
+        d.borrow_mut().edges.push((-2, c));
+        let (labels, dist) = a.borrow().all_pairs_shortest_paths();
+        assert_eq!(labels, vec!["A", "B", "D", "C"]);
+        assert_eq!(dist[0], vec![Some(0), Some(3), Some(4), Some(1)]);
+        assert_eq!(dist[1], vec![None, Some(0), Some(1), Some(-1)]);
+        assert_eq!(dist[2], vec![None, Some(0), Some(0), Some(-2)]);
+        assert_eq!(dist[3], vec![None, Some(2), Some(3), Some(0)]);
+        d.borrow_mut().edges.clear();
+        let (_, dist) = init().borrow().all_pairs_shortest_paths();
+        assert_eq!(dist[0][3], Some(6));
+    }
+
+    #[test]
+    fn k_shortest_paths_in_cost_order() {
+        let g = init();
+        let g = g.borrow();
+        assert_eq!(g.k_shortest_paths("E", 5),
+                   vec![(6, vec!["A", "B", "D", "E"]), (10, vec!["A", "C", "E"])]);
+        assert_eq!(g.k_shortest_paths("E", 1), vec![(6, vec!["A", "B", "D", "E"])]);
+        assert!(g.k_shortest_paths("E", 0).is_empty());
+        assert!(g.k_shortest_paths("Z", 3).is_empty());
+        assert_eq!(g.k_shortest_paths("A", 3), vec![(0, vec!["A"])]);
+
+        // The example from Yen's paper.
+        let c = Node::new("C");
+        let d = Node::new("D");
+        let e = Node::new("E");
+        let f = Node::new("F");
+        let g = Node::new("G");
+        let h = Node::new("H");
+        for &(from, w, to) in &[(&c, 3, &d), (&c, 2, &e), (&d, 4, &f), (&e, 1, &d), (&e, 2, &f),
+                                (&e, 3, &g), (&f, 2, &g), (&f, 1, &h), (&g, 2, &h)] {
+            from.borrow_mut().edges.push((w, to.clone()));
+        }
+        let paths = c.borrow().k_shortest_paths("H", 3);
+        assert_eq!(paths.iter().map(|p| p.0).collect::<Vec<_>>(), vec![5, 7, 8]);
+        assert_eq!(paths[0].1, vec!["C", "E", "F", "H"]);
+        let all = c.borrow().k_shortest_paths("H", 100);
+        assert!(all.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn longest_path_in_a_dag() {
+        assert_eq!(init().borrow().longest_path("E"), None);
+        let s = Node::new("S");
+        let a = Node::new("A");
+        let b = Node::new("B");
+        let t = Node::new("T");
+        s.borrow_mut().edges.push((1, a.clone()));
+        s.borrow_mut().edges.push((2, b.clone()));
+        a.borrow_mut().edges.push((5, t.clone()));
+        a.borrow_mut().edges.push((2, b.clone()));
+        b.borrow_mut().edges.push((1, t));
+        let s = s.borrow();
+        assert_eq!(s.dijkstra("T"), Some((3, vec!["S", "B", "T"])));
+        assert_eq!(s.longest_path("T"), Some((6, vec!["S", "A", "T"])));
+        assert_eq!(s.longest_path("B"), Some((3, vec!["S", "A", "B"])));
+        assert_eq!(s.longest_path("S"), Some((0, vec!["S"])));
+        assert_eq!(s.longest_path("Z"), None);
+    }
+}